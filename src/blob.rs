@@ -0,0 +1,242 @@
+use crate::config::zp_dir;
+use crate::dedup::content_hash;
+use crate::history::{ClipboardHistoryEntry, EntrySource};
+use crate::store::{restrict_dir_permissions, write_atomic};
+use arboard::ImageData;
+use image::{ImageFormat, RgbaImage};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// MIME type of image entries; images are always stored as PNG.
+pub const IMAGE_MIME_TYPE: &str = "image/png";
+
+/// Blobs younger than this are never removed as unreferenced, since the entry
+/// pointing to them may not have been saved yet.
+const ORPHAN_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Directory holding binary entry contents (`~/.zp/blobs`).
+pub fn blobs_dir() -> PathBuf {
+    zp_dir().join("blobs")
+}
+
+/// Path of the blob for an entry with the given content `hash`.
+pub fn blob_path(dir: &Path, hash: &str) -> PathBuf {
+    dir.join(format!("{}.png", hash))
+}
+
+/// Decodes a PNG, JPEG or BMP file. Returns `None` if `bytes` aren't a supported image.
+pub fn decode_image(bytes: &[u8]) -> Option<io::Result<ImageData<'static>>> {
+    let format = image::guess_format(bytes).ok()?;
+    let decoded = image::load_from_memory_with_format(bytes, format)
+        .map(|image| {
+            let rgba = image.into_rgba8();
+            ImageData {
+                width: rgba.width() as usize,
+                height: rgba.height() as usize,
+                bytes: rgba.into_raw().into(),
+            }
+        })
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    Some(decoded)
+}
+
+/// Encodes RGBA clipboard image data as PNG.
+pub fn encode_png(image: &ImageData) -> io::Result<Vec<u8>> {
+    let rgba = RgbaImage::from_raw(
+        image.width as u32,
+        image.height as u32,
+        image.bytes.to_vec(),
+    )
+    .ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "image data doesn't match its dimensions",
+        )
+    })?;
+
+    let mut png = Cursor::new(Vec::new());
+    rgba.write_to(&mut png, ImageFormat::Png)
+        .map_err(io::Error::other)?;
+    Ok(png.into_inner())
+}
+
+/// Stores `image` as a blob in `dir` and returns a history entry referring to it.
+///
+/// Blobs are named after the hash of their PNG data, so copying the same image
+/// again reuses the existing blob.
+pub fn store_image(
+    dir: &Path,
+    image: &ImageData,
+    source: EntrySource,
+) -> io::Result<ClipboardHistoryEntry> {
+    let png = encode_png(image)?;
+    let mut entry =
+        ClipboardHistoryEntry::new(format!("[image {}x{}]", image.width, image.height), source);
+    entry.hash = content_hash(&png);
+    entry.size = png.len();
+    entry.mime_type = IMAGE_MIME_TYPE.to_string();
+    entry.dimensions = Some((image.width, image.height));
+
+    fs::create_dir_all(dir)?;
+    restrict_dir_permissions(dir)?;
+    let path = blob_path(dir, &entry.hash);
+    if !path.exists() {
+        // Private like the history itself
+        write_atomic(&path, &png)?;
+    }
+    Ok(entry)
+}
+
+/// Reads the PNG data of an image entry.
+pub fn read_png(dir: &Path, entry: &ClipboardHistoryEntry) -> io::Result<Vec<u8>> {
+    fs::read(blob_path(dir, &entry.hash)).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("image of entry {} is missing: {}", entry.id, e),
+        )
+    })
+}
+
+/// Loads an image entry as clipboard image data.
+pub fn load_image(dir: &Path, entry: &ClipboardHistoryEntry) -> io::Result<ImageData<'static>> {
+    decode_image(&read_png(dir, entry)?).unwrap_or_else(|| {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("image of entry {} is not a PNG file", entry.id),
+        ))
+    })
+}
+
+/// Deletes blobs in `dir` that none of `entries` refer to any more.
+///
+/// Returns the number of blobs removed.
+pub fn remove_unreferenced_blobs(
+    dir: &Path,
+    entries: &[ClipboardHistoryEntry],
+) -> io::Result<usize> {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+
+    let referenced: HashSet<PathBuf> = entries
+        .iter()
+        .filter(|entry| entry.is_image())
+        .map(|entry| blob_path(dir, &entry.hash))
+        .collect();
+    let now = SystemTime::now();
+
+    let mut removed = 0;
+    for dir_entry in read_dir {
+        let dir_entry = dir_entry?;
+        let path = dir_entry.path();
+        let is_recent = dir_entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| now.duration_since(modified).unwrap_or_default() < ORPHAN_GRACE_PERIOD)
+            .unwrap_or(true);
+        if !is_recent && !referenced.contains(&path) {
+            fs::remove_file(path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Deletes the blobs of the `removed` entries that none of `entries` refer to.
+///
+/// Unlike [`remove_unreferenced_blobs`], other blobs are left alone, however old.
+pub fn remove_blobs_of(
+    dir: &Path,
+    removed: &[ClipboardHistoryEntry],
+    entries: &[ClipboardHistoryEntry],
+) -> io::Result<usize> {
+    let referenced: HashSet<&str> = entries
+        .iter()
+        .filter(|entry| entry.is_image())
+        .map(|entry| entry.hash.as_str())
+        .collect();
+
+    let mut removed_blobs = 0;
+    for entry in removed.iter().filter(|entry| entry.is_image()) {
+        if referenced.contains(entry.hash.as_str()) {
+            continue;
+        }
+        match fs::remove_file(blob_path(dir, &entry.hash)) {
+            Ok(()) => removed_blobs += 1,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(removed_blobs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkerboard() -> ImageData<'static> {
+        let bytes = (0..4)
+            .flat_map(|i| {
+                if i % 2 == 0 {
+                    [0, 0, 0, 255]
+                } else {
+                    [255, 255, 255, 255]
+                }
+            })
+            .collect::<Vec<u8>>();
+        ImageData {
+            width: 2,
+            height: 2,
+            bytes: bytes.into(),
+        }
+    }
+
+    #[test]
+    fn test_image_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = checkerboard();
+        let entry = store_image(dir.path(), &image, EntrySource::Manual).unwrap();
+        assert!(entry.is_image());
+        assert_eq!(entry.dimensions, Some((2, 2)));
+        assert_eq!(entry.size, read_png(dir.path(), &entry).unwrap().len());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&blob_path(dir.path(), &entry.hash)), 0o600);
+            assert_eq!(mode(dir.path()), 0o700);
+        }
+
+        let loaded = load_image(dir.path(), &entry).unwrap();
+        assert_eq!((loaded.width, loaded.height), (2, 2));
+        assert_eq!(loaded.bytes, image.bytes);
+    }
+
+    #[test]
+    fn test_remove_blobs_of() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = store_image(dir.path(), &checkerboard(), EntrySource::Manual).unwrap();
+        let path = blob_path(dir.path(), &entry.hash);
+        let entries = vec![entry.clone(), entry];
+
+        // Still referenced by the other copy of the image
+        assert_eq!(
+            remove_blobs_of(dir.path(), &entries[..1], &entries[1..]).unwrap(),
+            0
+        );
+        assert!(path.exists());
+
+        assert_eq!(remove_blobs_of(dir.path(), &entries, &[]).unwrap(), 1);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_decode_ignores_text() {
+        assert!(decode_image(b"just some text").is_none());
+    }
+}
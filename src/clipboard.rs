@@ -1,35 +1,124 @@
-use crate::history::save_clipboard_history;
-use arboard::Clipboard;
-use std::process;
-
-pub fn cpy(contents: &str, start: usize, end: usize) {
-    let mut clipboard = Clipboard::new().unwrap();
-
-    if end == 0 {
-        if start == 0 {
-            clipboard.set_text(contents).unwrap_or_else(|err| {
-                eprintln!("Couldn't copy to clipboard: {}", err);
-                process::exit(1);
-            });
-        } else {
-            let words: Vec<&str> = contents.split_whitespace().take(start).collect();
-            clipboard.set_text(words.join(" ")).unwrap_or_else(|err| {
-                eprintln!("Couldn't copy to clipboard: {}", err);
-                process::exit(1);
-            });
-        }
-    } else {
-        let lines: Vec<&str> = contents
-            .lines()
-            .enumerate()
-            .filter(|&(i, _)| i >= start && i <= end)
-            .map(|(_, line)| line)
-            .collect();
-        clipboard.set_text(lines.join("\n")).unwrap_or_else(|err| {
-            eprintln!("Couldn't copy to clipboard: {}", err);
-            process::exit(1);
-        });
+use crate::config::{HistoryEncryption, ZpConfig};
+use crate::crypto::PASSPHRASE_ENV;
+use crate::dedup::content_hash;
+use crate::error::ZpError;
+use crate::history::{
+    prune_clipboard_history, save_clipboard_history, save_image_history, ClipboardHistoryEntry,
+    EntrySource, SourceRef,
+};
+use crate::provider::open_clipboard;
+use arboard::ImageData;
+use chrono::Duration;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Copies `text` and records exactly that text in history, along with the
+/// `sources` it was read from.
+///
+/// With `expire`, the entry is removed from the history once that time has passed,
+/// and a detached process clears the clipboard then if it still holds `text`.
+pub fn cpy(text: String, sources: Vec<SourceRef>, expire: Option<Duration>) -> Result<(), ZpError> {
+    let entry = ClipboardHistoryEntry::new(text.clone(), EntrySource::Manual).with_sources(sources);
+    let Some(ttl) = expire else {
+        open_clipboard()?
+            .set_text(&text)
+            .map_err(ZpError::Clipboard)?;
+        return save_clipboard_history(entry);
+    };
+
+    // Recorded before copying, so the daemon knows the copy is ephemeral when it sees it
+    save_clipboard_history(entry.expiring_in(ttl)?)?;
+    open_clipboard()?
+        .set_text(&text)
+        .map_err(ZpError::Clipboard)?;
+    spawn_clearer(&text, ttl)
+}
+
+/// Starts `zp --clear-after` in the background for `text`.
+fn spawn_clearer(text: &str, ttl: Duration) -> Result<(), ZpError> {
+    let mut command = Command::new(env::current_exe()?);
+    command
+        .arg("--clear-after")
+        .arg(ttl.num_seconds().to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // Its own process group keeps it running after Ctrl-C in the terminal
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = command.spawn()?;
+    // Through stdin, since other users can read the command line
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(content_hash(text.as_bytes()).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Waits for `seconds`, then removes expired entries from the history and clears
+/// the clipboard if it still holds the content whose hash is read from stdin.
+pub fn clear_after(seconds: u64) -> Result<(), ZpError> {
+    let mut hash = String::new();
+    io::stdin().read_to_string(&mut hash)?;
+    thread::sleep(std::time::Duration::from_secs(seconds));
+
+    // With no terminal to ask for the passphrase, the expired entry stays on disk
+    // until the daemon or the next command removes it; it is hidden either way
+    let history = ZpConfig::load()?.history;
+    if history.encryption != HistoryEncryption::Passphrase || env::var(PASSPHRASE_ENV).is_ok() {
+        prune_clipboard_history()?;
     }
 
-    save_clipboard_history(contents.to_string());
+    let mut clipboard = open_clipboard()?;
+    // Whatever was copied since is left alone, and a clipboard that can't be read
+    // back, such as OSC 52, can't be checked
+    let current = clipboard.get_text().ok().flatten();
+    if current.is_some_and(|text| content_hash(text.as_bytes()) == hash.trim()) {
+        clipboard.set_text("").map_err(ZpError::Clipboard)?;
+    }
+    Ok(())
+}
+
+/// Source references for a selection of `lines` (1-based, inclusive) from `path`,
+/// or for the whole file when `lines` is empty.
+pub fn source_refs(path: &str, lines: &[(usize, usize)]) -> Vec<SourceRef> {
+    let path = canonical_path(path);
+    if lines.is_empty() {
+        return vec![SourceRef { path, lines: None }];
+    }
+    lines
+        .iter()
+        .map(|&lines| SourceRef {
+            path: path.clone(),
+            lines: Some(lines),
+        })
+        .collect()
+}
+
+/// Copies an image read from `path` and records it in history.
+pub fn cpy_image(image: ImageData, path: &str) -> Result<(), ZpError> {
+    open_clipboard()?
+        .set_image(image.clone())
+        .map_err(ZpError::Clipboard)?;
+
+    save_image_history(
+        &image,
+        EntrySource::Manual,
+        vec![SourceRef {
+            path: canonical_path(path),
+            lines: None,
+        }],
+    )
+}
+
+fn canonical_path(path: &str) -> String {
+    fs::canonicalize(path)
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| path.to_string())
 }
@@ -0,0 +1,190 @@
+use crate::blob::{blobs_dir, read_png};
+use crate::config::{HistoryEncryption, ZpConfig};
+use crate::crypto::{read_passphrase, PASSPHRASE_ENV};
+use crate::duration::parse_time;
+use crate::error::ZpError;
+use crate::history::{
+    format_elapsed_time, load_clipboard_history, remove_from_clipboard_history, resolve_entry,
+    set_history_encryption, ClipboardHistoryEntry,
+};
+use crate::query::HistoryCommand;
+use crate::secrets::reveal;
+use crate::store::encrypted::set_passphrase;
+use chrono::DateTime;
+use is_terminal::IsTerminal;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+
+const PREVIEW_WIDTH: usize = 60;
+
+pub fn run_history_command(command: &HistoryCommand) -> Result<(), ZpError> {
+    match command {
+        HistoryCommand::List { limit, json } => {
+            let entries = load_history()?;
+            print_entries(entries.iter().rev().enumerate(), *limit, *json)
+        }
+        HistoryCommand::Show {
+            entry,
+            json,
+            output,
+        } => {
+            let entries = load_history()?;
+            let entry = &entries[resolve_entry(&entries, entry)?];
+            if *json {
+                println!("{}", serde_json::to_string_pretty(entry)?);
+                return Ok(());
+            }
+
+            let data = if entry.is_image() {
+                if output.is_none() && io::stdout().is_terminal() {
+                    return Err(ZpError::Parse(
+                        "Entry is an image, write it to a file with --output".to_string(),
+                    ));
+                }
+                read_png(&blobs_dir(), entry)?
+            } else {
+                reveal(entry, &ZpConfig::load()?.secrets)?.into_bytes()
+            };
+            match output {
+                Some(path) => fs::write(path, data)?,
+                None => io::stdout().write_all(&data)?,
+            }
+            Ok(())
+        }
+        HistoryCommand::Search {
+            pattern,
+            ignore_case,
+            limit,
+            json,
+        } => {
+            let entries = load_history()?;
+            let pattern = if *ignore_case {
+                pattern.to_lowercase()
+            } else {
+                pattern.clone()
+            };
+            let matches = entries.iter().rev().enumerate().filter(|(_, entry)| {
+                if *ignore_case {
+                    entry.content.to_lowercase().contains(&pattern)
+                } else {
+                    entry.content.contains(&pattern)
+                }
+            });
+            print_entries(matches, *limit, *json)
+        }
+        HistoryCommand::Rm { entry } => {
+            let removed = remove_from_clipboard_history(|history| {
+                resolve_entry(history, entry).map(|position| history.remove(position))
+            })??;
+            println!("Deleted entry {}", removed.id);
+            Ok(())
+        }
+        HistoryCommand::Clear { before } => {
+            let cutoff = before.as_deref().map(parse_time).transpose()?;
+            let removed = remove_from_clipboard_history(|history| {
+                let len = history.len();
+                match cutoff {
+                    Some(cutoff) => history.retain(|entry| {
+                        DateTime::parse_from_rfc3339(&entry.timestamp)
+                            .map(|dt| dt >= cutoff)
+                            .unwrap_or(true)
+                    }),
+                    None => history.clear(),
+                }
+                len - history.len()
+            })?;
+            println!("Deleted {} entries", removed);
+            Ok(())
+        }
+        HistoryCommand::Encrypt {
+            passphrase,
+            key_file,
+        } => {
+            let encryption = if *passphrase {
+                HistoryEncryption::Passphrase
+            } else {
+                HistoryEncryption::Keyfile
+            };
+            if ZpConfig::load()?.history.encryption == encryption {
+                return Err(ZpError::Parse(
+                    "The history is already encrypted this way".to_string(),
+                ));
+            }
+            if *passphrase {
+                set_passphrase(new_passphrase()?);
+            }
+            let moved = set_history_encryption(encryption, key_file.clone())?;
+            println!("Encrypted {} entries", moved);
+            Ok(())
+        }
+        HistoryCommand::Decrypt => {
+            if ZpConfig::load()?.history.encryption == HistoryEncryption::None {
+                return Err(ZpError::Parse("The history is not encrypted".to_string()));
+            }
+            let moved = set_history_encryption(HistoryEncryption::None, None)?;
+            println!("Decrypted {} entries", moved);
+            Ok(())
+        }
+    }
+}
+
+/// Asks for a new passphrase twice, unless it is given in `ZP_PASSPHRASE`.
+fn new_passphrase() -> Result<String, ZpError> {
+    let passphrase = read_passphrase("New passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(ZpError::Parse("The passphrase can't be empty".to_string()));
+    }
+    if env::var(PASSPHRASE_ENV).is_err() && read_passphrase("Repeat passphrase: ")? != passphrase {
+        return Err(ZpError::Parse("The passphrases don't match".to_string()));
+    }
+    Ok(passphrase)
+}
+
+/// Loads the history, treating a missing history file as an empty history.
+pub(crate) fn load_history() -> Result<Vec<ClipboardHistoryEntry>, ZpError> {
+    match load_clipboard_history() {
+        Err(ZpError::Io(e)) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        result => result,
+    }
+}
+
+/// Prints `(index, entry)` pairs, most recent first, as a table or as a JSON array.
+fn print_entries<'a>(
+    entries: impl Iterator<Item = (usize, &'a ClipboardHistoryEntry)>,
+    limit: Option<usize>,
+    json: bool,
+) -> Result<(), ZpError> {
+    let entries = entries.take(limit.unwrap_or(usize::MAX));
+
+    if json {
+        let entries: Vec<&ClipboardHistoryEntry> = entries.map(|(_, entry)| entry).collect();
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    let mut stdout = io::stdout().lock();
+    for (index, entry) in entries {
+        writeln!(
+            stdout,
+            "{:>4}  {}  {}  {}",
+            index,
+            &entry.id[..entry.id.len().min(8)],
+            format_elapsed_time(&entry.timestamp),
+            preview(&entry.content)
+        )?;
+    }
+    Ok(())
+}
+
+/// First line of `content`, shortened to fit on one row of the listing.
+fn preview(content: &str) -> String {
+    let first_line = content.lines().next().unwrap_or("");
+    let truncated = first_line.chars().count() > PREVIEW_WIDTH || content.lines().nth(1).is_some();
+
+    let mut preview: String = first_line.chars().take(PREVIEW_WIDTH).collect();
+    if truncated {
+        preview.push('…');
+    }
+    preview
+}
@@ -0,0 +1,66 @@
+use crate::blob::{blobs_dir, encode_png, read_png};
+use crate::commands::history::load_history;
+use crate::config::ZpConfig;
+use crate::error::ZpError;
+use crate::history::resolve_entry;
+use crate::provider::open_clipboard;
+use crate::secrets::reveal;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Writes the clipboard, or the history entry referred to by `entry`, to stdout or
+/// to the file `to`.
+///
+/// Images are written as PNG.
+pub fn run_paste_command(
+    entry: Option<&str>,
+    to: Option<&Path>,
+    append: bool,
+) -> Result<(), ZpError> {
+    let data = match entry {
+        Some(reference) => {
+            let entries = load_history()?;
+            let entry = &entries[resolve_entry(&entries, reference)?];
+            if entry.is_image() {
+                read_png(&blobs_dir(), entry)?
+            } else {
+                reveal(entry, &ZpConfig::load()?.secrets)?.into_bytes()
+            }
+        }
+        None => read_clipboard()?,
+    };
+
+    match to {
+        Some(path) => {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .append(append)
+                .truncate(!append)
+                .open(path)?;
+            file.write_all(&data)?;
+        }
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&data)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the current clipboard as text, or as PNG data when it holds an image.
+fn read_clipboard() -> Result<Vec<u8>, ZpError> {
+    let mut clipboard = open_clipboard()?;
+    if let Some(text) = clipboard.get_text().map_err(ZpError::Clipboard)? {
+        return Ok(text.into_bytes());
+    }
+    match clipboard.get_image().map_err(ZpError::Clipboard)? {
+        Some(image) => Ok(encode_png(&image)?),
+        None => Err(ZpError::Clipboard(io::Error::new(
+            io::ErrorKind::NotFound,
+            "the clipboard is empty",
+        ))),
+    }
+}
@@ -0,0 +1,151 @@
+use crate::error::ZpError;
+use clap::ValueEnum;
+use std::path::Path;
+
+/// Largest combined size copied from several files unless `--max-size` says otherwise.
+pub const DEFAULT_MAX_SIZE: u64 = 5 * 1024 * 1024;
+
+/// How each file is introduced when several files are copied together.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeaderStyle {
+    /// No header; the files are only separated by a newline
+    None,
+    /// A `==> path <==` line before each file
+    #[default]
+    Path,
+    /// The path followed by the file in a fenced code block tagged with its language
+    Fenced,
+}
+
+/// Formats the `text` read from `path` for inclusion in a combined copy.
+pub fn format_file(path: &str, text: &str, style: HeaderStyle) -> String {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    match style {
+        HeaderStyle::None => text.to_string(),
+        HeaderStyle::Path => format!("==> {} <==\n{}", path, text),
+        HeaderStyle::Fenced => format!("{}\n{}", path, code_fence(text, language_tag(path))),
+    }
+}
+
+/// Wraps `text` in a Markdown code block tagged with `language`.
+pub fn code_fence(text: &str, language: &str) -> String {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    // Use a longer fence than any backtick run inside the text
+    let longest_run = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!("{}{}\n{}\n{}", fence, language, text, fence)
+}
+
+/// Joins files formatted with [`format_file`].
+pub fn join_files(parts: &[String], style: HeaderStyle) -> String {
+    let separator = match style {
+        HeaderStyle::None => "\n",
+        HeaderStyle::Path | HeaderStyle::Fenced => "\n\n",
+    };
+    parts.join(separator)
+}
+
+/// Markdown code block language for a file, based on its extension.
+pub fn language_tag(path: &str) -> &'static str {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "ts" => "typescript",
+        "tsx" => "tsx",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "swift" => "swift",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "lua" => "lua",
+        "sh" | "bash" | "zsh" => "bash",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "sql" => "sql",
+        "md" => "markdown",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "xml" => "xml",
+        _ => "",
+    }
+}
+
+/// Parses a size such as `500000`, `512K`, `10M` or `1G` (powers of 1024).
+pub fn parse_size(text: &str) -> Result<u64, ZpError> {
+    let text = text.trim();
+    let (number, multiplier) = match text.char_indices().last() {
+        Some((i, unit)) if unit.is_ascii_alphabetic() => {
+            let multiplier = match unit.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                _ => {
+                    return Err(ZpError::Parse(format!(
+                        "unknown size unit '{}' in '{}'",
+                        unit, text
+                    )))
+                }
+            };
+            (&text[..i], multiplier)
+        }
+        _ => (text, 1),
+    };
+    let number = number
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| ZpError::Parse(format!("invalid size '{}'", text)))?;
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| ZpError::Parse(format!("size '{}' is too large", text)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers() {
+        let parts = vec![
+            format_file("src/main.rs", "fn main() {}\n", HeaderStyle::Path),
+            format_file("README.md", "# zp", HeaderStyle::Path),
+        ];
+        assert_eq!(
+            join_files(&parts, HeaderStyle::Path),
+            "==> src/main.rs <==\nfn main() {}\n\n==> README.md <==\n# zp"
+        );
+
+        assert_eq!(
+            format_file("src/main.rs", "fn main() {}", HeaderStyle::Fenced),
+            "src/main.rs\n```rust\nfn main() {}\n```"
+        );
+        // Fences inside the file don't end the block early
+        assert_eq!(
+            format_file("notes.md", "```\ncode\n```", HeaderStyle::Fenced),
+            "notes.md\n````markdown\n```\ncode\n```\n````"
+        );
+        assert_eq!(format_file("a.txt", "a\n", HeaderStyle::None), "a");
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1000").unwrap(), 1000);
+        assert_eq!(parse_size("512K").unwrap(), 512 * 1024);
+        assert_eq!(parse_size("10m").unwrap(), 10 * 1024 * 1024);
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("ten").is_err());
+        assert!(parse_size("99999999999999999G").is_err());
+    }
+}
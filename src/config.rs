@@ -1,9 +1,253 @@
+use crate::duration::parse_duration;
+use crate::error::ZpError;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::PathBuf;
 
+/// Directory holding zp's history, configuration and pid files (`~/.zp`).
+pub fn zp_dir() -> PathBuf {
+    env::var("HOME")
+        .map(|home| PathBuf::from(home).join(".zp"))
+        .unwrap_or_else(|_| PathBuf::from(".zp"))
+}
+
+/// General zp settings, stored in `~/.zp/config.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ZpConfig {
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub clipboard: ClipboardConfig,
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+}
+
+/// What the clipboard daemon leaves out of the history.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DaemonConfig {
+    /// Skip content that password managers mark as sensitive.
+    #[serde(default = "default_true")]
+    pub skip_sensitive: bool,
+    /// MIME types marking content as sensitive, besides the known password manager hints.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sensitive_types: Vec<String>,
+    /// Applications, by name or window class, whose copies aren't recorded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore_apps: Vec<String>,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            skip_sensitive: true,
+            sensitive_types: vec![],
+            ignore_apps: vec![],
+        }
+    }
+}
+
+/// How copies that look like passwords, tokens or keys are handled, see [`crate::secrets`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SecretsConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub action: SecretAction,
+    /// Also flag long random-looking strings, not only known token formats.
+    #[serde(default = "default_true")]
+    pub high_entropy: bool,
+    /// Key used by the `encrypt` action, `~/.zp/secret.key` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<PathBuf>,
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            action: SecretAction::default(),
+            high_entropy: true,
+            key_file: None,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// What happens to an entry in which a secret was found.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretAction {
+    /// Don't record the entry at all.
+    Skip,
+    /// Record the entry with each secret replaced by a `[REDACTED:kind]` marker.
+    #[default]
+    Redact,
+    /// Record the redacted entry along with the original encrypted with a local key,
+    /// so it can still be pasted from history on this machine.
+    Encrypt,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ClipboardConfig {
+    #[serde(default)]
+    pub provider: ClipboardProviderKind,
+    /// File used by the `file` provider, `~/.zp/clipboard` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+}
+
+/// Which clipboard zp copies to and reads from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClipboardProviderKind {
+    /// Pick one based on the session, see `provider::candidates`.
+    #[default]
+    Auto,
+    /// The desktop clipboard on macOS, Windows and X11.
+    System,
+    /// OSC 52 terminal escape sequences, which reach the local clipboard over SSH.
+    Osc52,
+    /// `wl-copy` and `wl-paste` on Wayland.
+    WlClipboard,
+    /// `xclip` on X11.
+    Xclip,
+    /// tmux paste buffers.
+    Tmux,
+    /// A plain file.
+    File,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HistoryConfig {
+    #[serde(default)]
+    pub backend: HistoryBackend,
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+    #[serde(default)]
+    pub dedup: DedupStrategy,
+    #[serde(default, skip_serializing_if = "HistoryEncryption::is_none")]
+    pub encryption: HistoryEncryption,
+    /// Key used with `keyfile` encryption, `~/.zp/history.key` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<PathBuf>,
+}
+
+/// Whether the history is encrypted at rest, and where its key comes from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryEncryption {
+    /// Plain files in the format chosen by `backend`.
+    #[default]
+    None,
+    /// Encrypted with a random key stored in `key_file`.
+    Keyfile,
+    /// Encrypted with a key derived from a passphrase, read from `ZP_PASSPHRASE` or
+    /// asked for on the terminal.
+    Passphrase,
+}
+
+impl HistoryEncryption {
+    pub fn is_none(&self) -> bool {
+        *self == HistoryEncryption::None
+    }
+}
+
+/// How repeated copies of the same content are recorded.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupStrategy {
+    /// Record every copy.
+    #[default]
+    None,
+    /// Fold a copy into the most recent entry when the content is the same.
+    Consecutive,
+    /// Fold a copy into any earlier entry with the same content, moving it to the front.
+    Global,
+}
+
+/// Limits applied when pruning history. Unset limits don't prune anything, and
+/// pinned entries are always kept.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Maximum number of unpinned entries to keep.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_entries: Option<usize>,
+    /// Maximum total size of all entries, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_total_bytes: Option<usize>,
+    /// Maximum age of an entry, as a duration such as `30d` or `12h`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<String>,
+    /// Entries larger than this many bytes are not recorded at all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_entry_bytes: Option<usize>,
+}
+
+/// On-disk format used to store clipboard history.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryBackend {
+    /// A single pretty-printed JSON array (`clipboard_history.json`).
+    #[default]
+    Json,
+    /// One JSON object per line, appended on every copy (`clipboard_history.jsonl`).
+    Jsonl,
+    /// An SQLite database (`clipboard_history.db`).
+    Sqlite,
+}
+
+impl ZpConfig {
+    pub fn config_file() -> PathBuf {
+        zp_dir().join("config.json")
+    }
+
+    /// Loads the configuration, falling back to defaults when the file doesn't exist.
+    pub fn load() -> Result<Self, ZpError> {
+        let config_file = Self::config_file();
+        if !config_file.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&config_file)?;
+        let config: Self = serde_json::from_str(&content)
+            .map_err(|e| ZpError::Config(format!("{}: {}", config_file.display(), e)))?;
+
+        if let Some(max_age) = &config.history.retention.max_age {
+            parse_duration(max_age)
+                .map_err(|e| ZpError::Config(format!("history.retention.max_age: {}", e)))?;
+        }
+
+        Ok(config)
+    }
+
+    /// Loads the configuration, warning about and ignoring a broken config file.
+    pub fn load_or_default() -> Self {
+        Self::load().unwrap_or_else(|e| {
+            eprintln!("Warning: failed to load zp config, using defaults: {}", e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), ZpError> {
+        let config_dir = zp_dir();
+        if !config_dir.exists() {
+            fs::create_dir_all(&config_dir)?;
+        }
+
+        let content =
+            serde_json::to_string_pretty(self).map_err(|e| ZpError::Config(e.to_string()))?;
+        fs::write(Self::config_file(), content)?;
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SyncConfig {
     pub enabled: bool,
@@ -60,16 +304,22 @@ impl Default for SyncConfig {
 
 impl SyncConfig {
     pub fn config_dir() -> PathBuf {
-        env::var("HOME")
-            .map(|home| PathBuf::from(home).join(".zp"))
-            .unwrap_or_else(|_| PathBuf::from(".zp"))
+        zp_dir()
     }
 
     pub fn config_file() -> PathBuf {
         Self::config_dir().join("sync_config.json")
     }
 
-    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+    /// Peer id of this machine, without creating a sync configuration if there is none.
+    pub fn local_peer_id() -> Option<String> {
+        let content = fs::read_to_string(Self::config_file()).ok()?;
+        serde_json::from_str::<SyncConfig>(&content)
+            .ok()
+            .map(|config| config.peer_id)
+    }
+
+    pub fn load() -> Result<Self, ZpError> {
         let config_file = Self::config_file();
 
         if !config_file.exists() {
@@ -104,7 +354,7 @@ impl SyncConfig {
         }
     }
 
-    fn migrate_from_old_format(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+    fn migrate_from_old_format(content: &str) -> Result<Self, ZpError> {
         // Try to parse as a generic JSON value first
         let json: serde_json::Value = serde_json::from_str(content)?;
 
@@ -141,14 +391,15 @@ impl SyncConfig {
         Ok(config)
     }
 
-    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn save(&self) -> Result<(), ZpError> {
         let config_dir = Self::config_dir();
         if !config_dir.exists() {
             fs::create_dir_all(&config_dir)?;
         }
 
         let config_file = Self::config_file();
-        let content = serde_json::to_string_pretty(self)?;
+        let content =
+            serde_json::to_string_pretty(self).map_err(|e| ZpError::Config(e.to_string()))?;
         fs::write(&config_file, content)?;
         Ok(())
     }
@@ -255,4 +506,14 @@ mod tests {
         assert_eq!(ssh_config.ssh_host, "remote.host");
         assert_eq!(ssh_config.ssh_port, Some(22));
     }
+
+    #[test]
+    fn test_history_backend_config() {
+        let config: ZpConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.history.backend, HistoryBackend::Json);
+
+        let config: ZpConfig =
+            serde_json::from_str(r#"{"history": {"backend": "sqlite"}}"#).unwrap();
+        assert_eq!(config.history.backend, HistoryBackend::Sqlite);
+    }
 }
@@ -0,0 +1,204 @@
+use crate::error::ZpError;
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Length of the XChaCha20-Poly1305 nonce stored in front of every ciphertext.
+const NONCE_LEN: usize = 24;
+/// Length of the random salt used to derive a key from a passphrase.
+pub const SALT_LEN: usize = 16;
+/// Environment variable read for the passphrase instead of prompting, e.g. for the daemon.
+pub const PASSPHRASE_ENV: &str = "ZP_PASSPHRASE";
+
+/// A 256-bit key for XChaCha20-Poly1305.
+#[derive(Clone)]
+pub struct Key([u8; 32]);
+
+impl Key {
+    pub fn generate() -> Self {
+        Self(XChaCha20Poly1305::generate_key(&mut OsRng).into())
+    }
+
+    /// Derives a key from `passphrase` with Argon2id.
+    pub fn derive(passphrase: &str, salt: &[u8; SALT_LEN]) -> Result<Self, ZpError> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| ZpError::Crypto(format!("failed to derive key: {}", e)))?;
+        Ok(Self(key))
+    }
+
+    /// Reads a key written by [`Key::create`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let bytes: [u8; 32] = bytes.try_into().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a 32-byte key", path.display()),
+            )
+        })?;
+        Ok(Self(bytes))
+    }
+
+    /// Loads the key at `path`, generating it first if the file doesn't exist.
+    pub fn load_or_create(path: &Path) -> io::Result<Self> {
+        match Self::load(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let key = Self::generate();
+                key.create(path)?;
+                Ok(key)
+            }
+            result => result,
+        }
+    }
+
+    /// Writes the key to a new file only readable by the current user.
+    pub fn create(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(path)?.write_all(&self.0)
+    }
+
+    /// Encrypts `plaintext` under a fresh random nonce, which is prepended to the result.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, ZpError> {
+        let cipher = XChaCha20Poly1305::new(&self.0.into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| ZpError::Crypto("failed to encrypt".to_string()))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypts data produced by [`Key::encrypt`], failing if it was encrypted with
+    /// another key or has been tampered with.
+    pub fn decrypt(&self, sealed: &[u8]) -> Result<Vec<u8>, ZpError> {
+        if sealed.len() < NONCE_LEN {
+            return Err(ZpError::Crypto("encrypted data is truncated".to_string()));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        XChaCha20Poly1305::new(&self.0.into())
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| ZpError::Crypto("wrong key or corrupted data".to_string()))
+    }
+}
+
+pub fn random_salt() -> [u8; SALT_LEN] {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+/// Reads a passphrase from `ZP_PASSPHRASE`, or else prompts for it on the terminal
+/// without echoing it.
+pub fn read_passphrase(prompt: &str) -> io::Result<String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+
+    let mut tty = OpenOptions::new()
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "no terminal to ask for the passphrase; set {}",
+                    PASSPHRASE_ENV
+                ),
+            )
+        })?;
+    write!(tty, "{}", prompt)?;
+    tty.flush()?;
+
+    enable_raw_mode()?;
+    let passphrase = read_hidden_line();
+    disable_raw_mode()?;
+    writeln!(tty)?;
+    passphrase
+}
+
+fn read_hidden_line() -> io::Result<String> {
+    let mut line = String::new();
+    loop {
+        if let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event::read()?
+        {
+            match code {
+                KeyCode::Enter => return Ok(line),
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+                }
+                KeyCode::Esc => {
+                    return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"))
+                }
+                KeyCode::Char(c) => line.push(c),
+                KeyCode::Backspace => {
+                    line.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_wrong_key() {
+        let key = Key::generate();
+        let sealed = key.encrypt(b"hunter2").unwrap();
+        assert_ne!(&sealed[NONCE_LEN..], b"hunter2");
+        assert_eq!(key.decrypt(&sealed).unwrap(), b"hunter2");
+        assert!(Key::generate().decrypt(&sealed).is_err());
+
+        let salt = random_salt();
+        let derived = Key::derive("passphrase", &salt).unwrap();
+        let sealed = derived.encrypt(b"x").unwrap();
+        assert_eq!(
+            Key::derive("passphrase", &salt)
+                .unwrap()
+                .decrypt(&sealed)
+                .unwrap(),
+            b"x"
+        );
+        assert!(Key::derive("Passphrase", &salt)
+            .unwrap()
+            .decrypt(&sealed)
+            .is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secret.key");
+        let created = Key::load_or_create(&path).unwrap();
+        assert_eq!(
+            Key::load(&path)
+                .unwrap()
+                .decrypt(&created.encrypt(b"x").unwrap())
+                .unwrap(),
+            b"x"
+        );
+    }
+}
@@ -1,13 +1,34 @@
-use crate::history::save_clipboard_history;
-use arboard::Clipboard;
+use crate::config::{DaemonConfig, ZpConfig};
+use crate::error::ZpError;
+use crate::history::{
+    prune_clipboard_history, save_clipboard_history, save_image_history, ClipboardHistoryEntry,
+    EntrySource,
+};
+use crate::provider::{open_clipboard, ClipboardProvider};
+use arboard::ImageData;
 use std::env;
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::PathBuf;
 use std::process;
+use std::time::Instant;
 use std::{thread, time::Duration};
 
-pub fn start_daemon() -> Result<(), Box<dyn std::error::Error>> {
+/// MIME types with which password managers mark clipboard content as sensitive:
+/// KDE's hint, set by KeePassXC among others and also offered on Wayland, the
+/// nspasteboard.org types used on macOS, and the Windows clipboard history opt-out.
+const SENSITIVE_TYPES: &[&str] = &[
+    "x-kde-passwordManagerHint",
+    "org.nspasteboard.ConcealedType",
+    "org.nspasteboard.TransientType",
+    "ExcludeClipboardContentFromMonitorProcessing",
+];
+
+/// How often the daemon applies the retention policy, so age limits take effect
+/// even when nothing new is copied.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+pub fn start_daemon() -> Result<(), ZpError> {
     // Check if daemon is already running
     let pid_dir = env::var("HOME")
         .map(|home| PathBuf::from(home).join(".zp"))
@@ -69,7 +90,7 @@ pub fn start_daemon() -> Result<(), Box<dyn std::error::Error>> {
             }
             Err(e) => {
                 eprintln!("Error starting daemon: {}", e);
-                Err(e.into())
+                Err(ZpError::Io(io::Error::other(e)))
             }
         }
     }
@@ -83,7 +104,7 @@ pub fn start_daemon() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 // The actual daemon worker process
-pub fn run_daemon_worker() -> Result<(), Box<dyn std::error::Error>> {
+pub fn run_daemon_worker() -> Result<(), ZpError> {
     // Get the pid file path
     let pid_dir = env::var("HOME")
         .map(|home| PathBuf::from(home).join(".zp"))
@@ -97,18 +118,40 @@ pub fn run_daemon_worker() -> Result<(), Box<dyn std::error::Error>> {
     write!(file, "{}", pid)?;
 
     // Initialize clipboard
-    let mut clipboard = Clipboard::new()?;
-    let mut last_content = String::new();
+    let mut clipboard = open_clipboard()?;
+    // OSC 52 can only write to the clipboard, so there is nothing to monitor
+    if let Err(e) = clipboard.get_text() {
+        if e.kind() == io::ErrorKind::Unsupported {
+            fs::remove_file(&pid_file)?;
+            return Err(ZpError::Clipboard(e));
+        }
+    }
+    let config = ZpConfig::load()?.daemon;
+    let mut monitor = Monitor::default();
+    let mut last_prune = Instant::now();
 
     // Monitor clipboard in the background
     loop {
-        match clipboard.get_text() {
-            Ok(current_content) => {
-                if !current_content.is_empty() && current_content != last_content {
-                    save_clipboard_history(current_content.clone());
-                    last_content = current_content;
+        if last_prune.elapsed() >= PRUNE_INTERVAL {
+            if let Err(e) = prune_clipboard_history() {
+                eprintln!("Error pruning clipboard history: {}", e);
+            }
+            last_prune = Instant::now();
+        }
+
+        match monitor.poll(clipboard.as_mut(), &config) {
+            Ok(Some(Capture::Text(text))) => {
+                let entry = ClipboardHistoryEntry::new(text, EntrySource::Daemon);
+                if let Err(e) = save_clipboard_history(entry) {
+                    eprintln!("Error saving clipboard history: {}", e);
+                }
+            }
+            Ok(Some(Capture::Image(image))) => {
+                if let Err(e) = save_image_history(&image, EntrySource::Daemon, vec![]) {
+                    eprintln!("Error storing clipboard image: {}", e);
                 }
             }
+            Ok(None) => {}
             Err(e) => {
                 eprintln!("Error reading clipboard: {}", e);
             }
@@ -118,7 +161,82 @@ pub fn run_daemon_worker() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
-pub fn stop_daemon() -> Result<(), Box<dyn std::error::Error>> {
+/// New clipboard content found by [`Monitor::poll`].
+#[derive(Debug)]
+enum Capture {
+    Text(String),
+    Image(ImageData<'static>),
+}
+
+/// Remembers what was last on the clipboard, so each change is captured once.
+#[derive(Default)]
+struct Monitor {
+    last_content: String,
+    last_image: Vec<u8>,
+}
+
+impl Monitor {
+    /// Reads the clipboard and returns its content if it changed since the last poll.
+    ///
+    /// Content marked as sensitive or copied from an ignored application is left out,
+    /// but still remembered so it isn't checked again on every poll.
+    fn poll(
+        &mut self,
+        clipboard: &mut dyn ClipboardProvider,
+        config: &DaemonConfig,
+    ) -> io::Result<Option<Capture>> {
+        let capture = match clipboard.get_text()? {
+            Some(text) if text.is_empty() || text == self.last_content => return Ok(None),
+            Some(text) => {
+                self.last_content = text.clone();
+                Capture::Text(text)
+            }
+            // Not text, so it may be an image
+            None => match clipboard.get_image()? {
+                Some(image) if image.bytes.as_ref() != self.last_image.as_slice() => {
+                    self.last_image = image.bytes.to_vec();
+                    Capture::Image(image)
+                }
+                _ => return Ok(None),
+            },
+        };
+
+        if let Some(reason) = exclusion(clipboard, config) {
+            eprintln!("Not recording clipboard change: {}", reason);
+            return Ok(None);
+        }
+        Ok(Some(capture))
+    }
+}
+
+/// Says why the current clipboard content shouldn't be recorded, if it shouldn't.
+fn exclusion(clipboard: &mut dyn ClipboardProvider, config: &DaemonConfig) -> Option<String> {
+    if config.skip_sensitive {
+        let types = clipboard.mime_types().unwrap_or_default();
+        let hint = types.iter().find(|mime_type| {
+            SENSITIVE_TYPES.contains(&mime_type.as_str())
+                || config.sensitive_types.contains(mime_type)
+        });
+        if let Some(hint) = hint {
+            return Some(format!("marked as sensitive with {}", hint));
+        }
+    }
+
+    if !config.ignore_apps.is_empty() {
+        if let Ok(Some(app)) = clipboard.source_application() {
+            if config
+                .ignore_apps
+                .iter()
+                .any(|ignored| ignored.eq_ignore_ascii_case(&app))
+            {
+                return Some(format!("copied from {}", app));
+            }
+        }
+    }
+    None
+}
+
+pub fn stop_daemon() -> Result<(), ZpError> {
     let pid_dir = env::var("HOME")
         .map(|home| PathBuf::from(home).join(".zp"))
         .unwrap_or_else(|_| PathBuf::from(".zp"));
@@ -171,7 +289,7 @@ pub fn stop_daemon() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-pub fn daemon_status() -> Result<(), Box<dyn std::error::Error>> {
+pub fn daemon_status() -> Result<(), ZpError> {
     let pid_dir = env::var("HOME")
         .map(|home| PathBuf::from(home).join(".zp"))
         .unwrap_or_else(|_| PathBuf::from(".zp"));
@@ -213,3 +331,121 @@ pub fn daemon_status() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[derive(Default)]
+    struct MockClipboard {
+        text: Option<String>,
+        image: Option<ImageData<'static>>,
+        types: Vec<String>,
+        app: Option<String>,
+    }
+
+    impl MockClipboard {
+        fn copy(&mut self, text: &str, types: &[&str], app: Option<&str>) {
+            self.text = Some(text.to_string());
+            self.types = types.iter().map(|t| t.to_string()).collect();
+            self.app = app.map(str::to_string);
+        }
+    }
+
+    impl ClipboardProvider for MockClipboard {
+        fn name(&self) -> &'static str {
+            "mock"
+        }
+
+        fn get_text(&mut self) -> io::Result<Option<String>> {
+            Ok(self.text.clone())
+        }
+
+        fn set_text(&mut self, text: &str) -> io::Result<()> {
+            self.text = Some(text.to_string());
+            Ok(())
+        }
+
+        fn get_image(&mut self) -> io::Result<Option<ImageData<'static>>> {
+            Ok(self.image.clone())
+        }
+
+        fn mime_types(&mut self) -> io::Result<Vec<String>> {
+            Ok(self.types.clone())
+        }
+
+        fn source_application(&mut self) -> io::Result<Option<String>> {
+            Ok(self.app.clone())
+        }
+    }
+
+    fn captured_text(capture: Option<Capture>) -> Option<String> {
+        match capture {
+            Some(Capture::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_captures_each_change_once() {
+        let mut clipboard = MockClipboard::default();
+        let mut monitor = Monitor::default();
+        let config = DaemonConfig::default();
+
+        clipboard.copy("hello", &["text/plain"], None);
+        let capture = monitor.poll(&mut clipboard, &config).unwrap();
+        assert_eq!(captured_text(capture).as_deref(), Some("hello"));
+        assert!(monitor.poll(&mut clipboard, &config).unwrap().is_none());
+
+        clipboard.text = None;
+        clipboard.image = Some(ImageData {
+            width: 1,
+            height: 1,
+            bytes: Cow::Owned(vec![0, 0, 0, 255]),
+        });
+        let capture = monitor.poll(&mut clipboard, &config).unwrap();
+        assert!(matches!(capture, Some(Capture::Image(_))));
+        assert!(monitor.poll(&mut clipboard, &config).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_skips_password_manager_hints() {
+        let mut clipboard = MockClipboard::default();
+        let mut monitor = Monitor::default();
+        let mut config = DaemonConfig::default();
+
+        clipboard.copy(
+            "hunter2",
+            &["text/plain", "x-kde-passwordManagerHint"],
+            None,
+        );
+        assert!(monitor.poll(&mut clipboard, &config).unwrap().is_none());
+
+        config.sensitive_types = vec!["application/x-secret".to_string()];
+        clipboard.copy("s3cret", &["application/x-secret"], None);
+        assert!(monitor.poll(&mut clipboard, &config).unwrap().is_none());
+
+        config.skip_sensitive = false;
+        clipboard.copy("visible", &["x-kde-passwordManagerHint"], None);
+        let capture = monitor.poll(&mut clipboard, &config).unwrap();
+        assert_eq!(captured_text(capture).as_deref(), Some("visible"));
+    }
+
+    #[test]
+    fn test_skips_ignored_applications() {
+        let mut clipboard = MockClipboard::default();
+        let mut monitor = Monitor::default();
+        let config = DaemonConfig {
+            ignore_apps: vec!["KeePassXC".to_string()],
+            ..DaemonConfig::default()
+        };
+
+        clipboard.copy("hunter2", &[], Some("keepassxc"));
+        assert!(monitor.poll(&mut clipboard, &config).unwrap().is_none());
+
+        clipboard.copy("notes", &[], Some("firefox"));
+        let capture = monitor.poll(&mut clipboard, &config).unwrap();
+        assert_eq!(captured_text(capture).as_deref(), Some("notes"));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::config::DedupStrategy;
+use crate::history::ClipboardHistoryEntry;
+use sha2::{Digest, Sha256};
+
+/// Hex-encoded SHA-256 of `content`, used to recognise the same content across
+/// entries and peers.
+pub fn content_hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Position in `history` (oldest first) of an entry that `entry` duplicates under `strategy`.
+///
+/// Entries that expire are never folded together with others, so they keep their
+/// own expiry and don't take it away from or add it to a permanent entry.
+pub fn find_duplicate(
+    history: &[ClipboardHistoryEntry],
+    entry: &ClipboardHistoryEntry,
+    strategy: DedupStrategy,
+) -> Option<usize> {
+    if entry.expires_at.is_some() {
+        return None;
+    }
+    let duplicates = |e: &ClipboardHistoryEntry| e.hash == entry.hash && e.expires_at.is_none();
+    match strategy {
+        DedupStrategy::None => None,
+        DedupStrategy::Consecutive => history
+            .last()
+            .filter(|last| duplicates(last))
+            .map(|_| history.len() - 1),
+        DedupStrategy::Global => history.iter().rposition(duplicates),
+    }
+}
+
+/// Folds a repeated copy into the existing entry at `position`: it takes the new
+/// `timestamp`, counts one more use and moves to the end of the history.
+pub fn bump_entry(history: &mut Vec<ClipboardHistoryEntry>, position: usize, timestamp: String) {
+    let mut existing = history.remove(position);
+    existing.timestamp = timestamp;
+    existing.use_count += 1;
+    history.push(existing);
+}
+
+/// Adds `entry` to `history`, folding it into an existing entry when `strategy`
+/// considers it a duplicate. Returns true if a new entry was added.
+pub fn add_entry(
+    history: &mut Vec<ClipboardHistoryEntry>,
+    entry: ClipboardHistoryEntry,
+    strategy: DedupStrategy,
+) -> bool {
+    match find_duplicate(history, &entry, strategy) {
+        Some(position) => {
+            bump_entry(history, position, entry.timestamp);
+            false
+        }
+        None => {
+            history.push(entry);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::EntrySource;
+
+    fn copy(history: &mut Vec<ClipboardHistoryEntry>, content: &str, strategy: DedupStrategy) {
+        let entry = ClipboardHistoryEntry::new(content.to_string(), EntrySource::Manual);
+        add_entry(history, entry, strategy);
+    }
+
+    fn copies(strategy: DedupStrategy) -> Vec<ClipboardHistoryEntry> {
+        let mut history = vec![];
+        for content in ["A", "B", "B", "A"] {
+            copy(&mut history, content, strategy);
+        }
+        history
+    }
+
+    fn contents(history: &[ClipboardHistoryEntry]) -> Vec<(&str, u32)> {
+        history
+            .iter()
+            .map(|e| (e.content.as_str(), e.use_count))
+            .collect()
+    }
+
+    #[test]
+    fn test_dedup_strategies() {
+        assert_eq!(
+            contents(&copies(DedupStrategy::None)),
+            vec![("A", 1), ("B", 1), ("B", 1), ("A", 1)]
+        );
+        assert_eq!(
+            contents(&copies(DedupStrategy::Consecutive)),
+            vec![("A", 1), ("B", 2), ("A", 1)]
+        );
+        assert_eq!(
+            contents(&copies(DedupStrategy::Global)),
+            vec![("B", 2), ("A", 2)]
+        );
+    }
+
+    #[test]
+    fn test_global_dedup_keeps_id() {
+        let mut history = vec![];
+        copy(&mut history, "A", DedupStrategy::Global);
+        let id = history[0].id.clone();
+        copy(&mut history, "B", DedupStrategy::Global);
+        copy(&mut history, "A", DedupStrategy::Global);
+        assert_eq!(history[1].id, id);
+    }
+
+    #[test]
+    fn test_expiring_entries_are_not_folded() {
+        let mut history = vec![];
+        copy(&mut history, "A", DedupStrategy::Global);
+        let expiring = ClipboardHistoryEntry::new("A".to_string(), EntrySource::Manual)
+            .expiring_in(chrono::Duration::seconds(30))
+            .unwrap();
+        assert!(add_entry(&mut history, expiring, DedupStrategy::Global));
+        copy(&mut history, "A", DedupStrategy::Consecutive);
+        assert_eq!(contents(&history), vec![("A", 1), ("A", 1), ("A", 1)]);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::error::ZpError;
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone};
+
+/// Parses a duration such as `30s`, `15m`, `12h`, `7d` or `2w`.
+pub fn parse_duration(input: &str) -> Result<Duration, ZpError> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+
+    let value: i64 = number.parse().map_err(|_| {
+        ZpError::Parse(format!(
+            "Invalid duration '{}': expected e.g. 30s, 15m, 12h, 7d",
+            input
+        ))
+    })?;
+
+    let duration = match unit {
+        "s" | "" => Duration::try_seconds(value),
+        "m" => Duration::try_minutes(value),
+        "h" => Duration::try_hours(value),
+        "d" => Duration::try_days(value),
+        "w" => Duration::try_weeks(value),
+        _ => {
+            return Err(ZpError::Parse(format!(
+                "Invalid duration unit '{}' in '{}': use s, m, h, d or w",
+                unit, input
+            )))
+        }
+    };
+    duration.ok_or_else(|| ZpError::Parse(format!("Duration '{}' is too long", input)))
+}
+
+/// Parses a point in time given as an RFC 3339 timestamp, a `YYYY-MM-DD` date
+/// (local midnight) or a duration relative to now (`7d` means seven days ago).
+pub fn parse_time(input: &str) -> Result<DateTime<Local>, ZpError> {
+    let input = input.trim();
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Ok(dt.with_timezone(&Local));
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+        return Local
+            .from_local_datetime(&midnight)
+            .earliest()
+            .ok_or_else(|| ZpError::Parse(format!("Invalid local date '{}'", input)));
+    }
+
+    parse_duration(input)
+        .ok()
+        .and_then(|duration| Local::now().checked_sub_signed(duration))
+        .ok_or_else(|| {
+            ZpError::Parse(format!(
+                "Invalid time '{}': use YYYY-MM-DD, an RFC 3339 timestamp or a duration like 7d",
+                input
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::seconds(30));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::minutes(15));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::days(7));
+        assert_eq!(parse_duration("45").unwrap(), Duration::seconds(45));
+        assert!(parse_duration("7y").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("999999999999d").is_err());
+        assert!(parse_duration("99999999999999w").is_err());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let date = parse_time("2024-03-01").unwrap();
+        assert_eq!(
+            date.format("%Y-%m-%d %H:%M").to_string(),
+            "2024-03-01 00:00"
+        );
+
+        let relative = parse_time("1d").unwrap();
+        assert!(relative < Local::now() - Duration::hours(23));
+        assert!(parse_time("yesterday").is_err());
+        assert!(parse_time("99999999999d").is_err());
+        assert!(parse_time("9999999999w").is_err());
+    }
+}
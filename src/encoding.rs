@@ -0,0 +1,184 @@
+use base64::Engine;
+use clap::ValueEnum;
+use std::fmt;
+
+/// Text encodings `--encoding` can decode input from.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// UTF-8, replacing invalid sequences
+    #[value(name = "utf-8", alias = "utf8")]
+    Utf8,
+    /// UTF-16 with the byte order taken from the BOM, little-endian without one
+    #[value(name = "utf-16", alias = "utf16")]
+    Utf16,
+    #[value(name = "utf-16le", alias = "utf16le")]
+    Utf16Le,
+    #[value(name = "utf-16be", alias = "utf16be")]
+    Utf16Be,
+    /// ISO-8859-1, where every byte is a character
+    #[value(name = "latin1", alias = "iso-8859-1")]
+    Latin1,
+}
+
+/// Text representations `--binary` can copy arbitrary bytes as.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryFormat {
+    Base64,
+    Hex,
+}
+
+/// Why input couldn't be turned into text without an explicit `--encoding` or `--binary`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input contains NUL bytes or mostly control characters.
+    Binary,
+    /// The input looks like text but isn't valid UTF-8.
+    InvalidUtf8 { offset: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Binary => write!(
+                f,
+                "input looks like binary data; use --binary base64 or --binary hex to copy it encoded"
+            ),
+            DecodeError::InvalidUtf8 { offset } => write!(
+                f,
+                "input is not valid UTF-8 (invalid byte at offset {}); use --encoding latin1 or \
+                 --encoding utf-16 to decode it, or --binary base64 to copy it encoded",
+                offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Turns input bytes into the text to copy.
+///
+/// With `binary` the bytes are encoded as base64 or hex. With `encoding` they are
+/// decoded from it, replacing invalid sequences. Otherwise the encoding is detected:
+/// UTF-8, or UTF-16 when the input starts with a byte order mark.
+pub fn bytes_to_text(
+    bytes: &[u8],
+    encoding: Option<TextEncoding>,
+    binary: Option<BinaryFormat>,
+) -> Result<String, DecodeError> {
+    if let Some(format) = binary {
+        return Ok(encode_binary(bytes, format));
+    }
+    match encoding {
+        Some(encoding) => Ok(decode_lossy(bytes, encoding)),
+        None => detect_and_decode(bytes),
+    }
+}
+
+/// Encodes `bytes` as base64 or lowercase hex.
+pub fn encode_binary(bytes: &[u8], format: BinaryFormat) -> String {
+    match format {
+        BinaryFormat::Base64 => base64::engine::general_purpose::STANDARD.encode(bytes),
+        BinaryFormat::Hex => bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+    }
+}
+
+/// Decodes `bytes` from `encoding`, dropping a leading byte order mark and replacing
+/// invalid sequences with U+FFFD.
+pub fn decode_lossy(bytes: &[u8], encoding: TextEncoding) -> String {
+    match encoding {
+        TextEncoding::Utf8 => {
+            String::from_utf8_lossy(bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes)).into_owned()
+        }
+        TextEncoding::Utf16 => match bytes.strip_prefix(UTF16_BE_BOM) {
+            Some(rest) => decode_utf16(rest, u16::from_be_bytes),
+            None => decode_utf16(
+                bytes.strip_prefix(UTF16_LE_BOM).unwrap_or(bytes),
+                u16::from_le_bytes,
+            ),
+        },
+        TextEncoding::Utf16Le => decode_utf16(
+            bytes.strip_prefix(UTF16_LE_BOM).unwrap_or(bytes),
+            u16::from_le_bytes,
+        ),
+        TextEncoding::Utf16Be => decode_utf16(
+            bytes.strip_prefix(UTF16_BE_BOM).unwrap_or(bytes),
+            u16::from_be_bytes,
+        ),
+        TextEncoding::Latin1 => bytes.iter().map(|&byte| byte as char).collect(),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units = bytes
+        .chunks(2)
+        .map(|pair| from_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]));
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+fn detect_and_decode(bytes: &[u8]) -> Result<String, DecodeError> {
+    if bytes.starts_with(UTF16_LE_BOM) || bytes.starts_with(UTF16_BE_BOM) {
+        return Ok(decode_lossy(bytes, TextEncoding::Utf16));
+    }
+
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(text.to_string()),
+        Err(_) if looks_binary(bytes) => Err(DecodeError::Binary),
+        Err(e) => Err(DecodeError::InvalidUtf8 {
+            offset: e.valid_up_to(),
+        }),
+    }
+}
+
+/// Guesses whether `bytes` are binary data rather than text in some legacy encoding.
+fn looks_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(8192)];
+    let control = sample
+        .iter()
+        .filter(|&&byte| byte < 0x20 && !matches!(byte, b'\n' | b'\r' | b'\t' | 0x0C))
+        .count();
+    sample.contains(&0) || control * 10 > sample.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_utf8_and_utf16() {
+        assert_eq!(bytes_to_text(b"plain", None, None).unwrap(), "plain");
+        assert_eq!(
+            bytes_to_text(b"\xEF\xBB\xBFbom", None, None).unwrap(),
+            "bom"
+        );
+        assert_eq!(bytes_to_text(b"\xFF\xFEh\0i\0", None, None).unwrap(), "hi");
+        assert_eq!(bytes_to_text(b"\xFE\xFF\0h\0i", None, None).unwrap(), "hi");
+    }
+
+    #[test]
+    fn test_rejects_undecodable_input() {
+        assert_eq!(
+            bytes_to_text(b"caf\xE9", None, None),
+            Err(DecodeError::InvalidUtf8 { offset: 3 })
+        );
+        assert_eq!(
+            bytes_to_text(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", None, None),
+            Err(DecodeError::Binary)
+        );
+    }
+
+    #[test]
+    fn test_explicit_encoding_and_binary_formats() {
+        assert_eq!(decode_lossy(b"caf\xE9", TextEncoding::Latin1), "café");
+        assert_eq!(decode_lossy(b"caf\xE9", TextEncoding::Utf8), "caf\u{FFFD}");
+        assert_eq!(decode_lossy(b"\0h\0i", TextEncoding::Utf16Be), "hi");
+        assert_eq!(encode_binary(b"\x00\xffzp", BinaryFormat::Hex), "00ff7a70");
+        assert_eq!(encode_binary(b"zp", BinaryFormat::Base64), "enA=");
+    }
+}
@@ -0,0 +1,104 @@
+//! Lists the copies made with `--expire` that haven't expired yet, so the daemon can
+//! recognise them without loading the whole history. Only hashes are kept, and each
+//! only until its entry expires.
+
+use crate::dedup::content_hash;
+use crate::history::{parse_entry_time, ClipboardHistoryEntry};
+use crate::store::write_atomic;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const EPHEMERAL_FILE_NAME: &str = "ephemeral.json";
+
+#[derive(Serialize, Deserialize)]
+struct EphemeralCopy {
+    hash: String,
+    expires_at: String,
+}
+
+impl EphemeralCopy {
+    fn is_live(&self, now: DateTime<Local>) -> bool {
+        parse_entry_time(&self.expires_at) > now
+    }
+}
+
+/// Reads the live copies in `dir`, treating a missing or unreadable file as empty.
+fn load(dir: &Path) -> Vec<EphemeralCopy> {
+    let now = Local::now();
+    fs::read(dir.join(EPHEMERAL_FILE_NAME))
+        .ok()
+        .and_then(|data| serde_json::from_slice::<Vec<EphemeralCopy>>(&data).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|copy| copy.is_live(now))
+        .collect()
+}
+
+fn write(dir: &Path, copies: &[EphemeralCopy]) -> io::Result<()> {
+    let path = dir.join(EPHEMERAL_FILE_NAME);
+    if copies.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    write_atomic(&path, &serde_json::to_vec(copies)?)
+}
+
+/// Remembers `entry`, which expires, until its expiry. The caller must hold the
+/// history lock.
+pub fn record(dir: &Path, entry: &ClipboardHistoryEntry) -> io::Result<()> {
+    let Some(expires_at) = &entry.expires_at else {
+        return Ok(());
+    };
+    let mut copies = load(dir);
+    copies.push(EphemeralCopy {
+        hash: content_hash(entry.content.as_bytes()),
+        expires_at: expires_at.clone(),
+    });
+    write(dir, &copies)
+}
+
+/// Returns true when `entry` has the content of a copy that is still to expire.
+pub fn is_live_copy(dir: &Path, entry: &ClipboardHistoryEntry) -> bool {
+    let hash = content_hash(entry.content.as_bytes());
+    load(dir).iter().any(|copy| copy.hash == hash)
+}
+
+/// Drops the copies that have expired. The caller must hold the history lock.
+pub fn forget_expired(dir: &Path) -> io::Result<()> {
+    if !dir.join(EPHEMERAL_FILE_NAME).exists() {
+        return Ok(());
+    }
+    write(dir, &load(dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::EntrySource;
+    use chrono::Duration;
+
+    #[test]
+    fn test_records_live_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry =
+            |content: &str| ClipboardHistoryEntry::new(content.to_string(), EntrySource::Daemon);
+        let expiring = entry("hunter2").expiring_in(Duration::seconds(30)).unwrap();
+
+        record(dir.path(), &expiring).unwrap();
+        assert!(is_live_copy(dir.path(), &entry("hunter2")));
+        assert!(!is_live_copy(dir.path(), &entry("other")));
+
+        let mut expired = entry("old");
+        expired.expires_at = Some((Local::now() - Duration::seconds(1)).to_rfc3339());
+        record(dir.path(), &expired).unwrap();
+        assert!(!is_live_copy(dir.path(), &expired));
+
+        forget_expired(dir.path()).unwrap();
+        assert_eq!(load(dir.path()).len(), 1);
+    }
+}
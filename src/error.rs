@@ -0,0 +1,118 @@
+use crate::encoding::DecodeError;
+use crate::range::RangeError;
+use crate::select::SelectError;
+use crate::store::is_corrupted;
+use std::fmt;
+use std::io;
+
+/// Errors returned by zp's public functions.
+///
+/// The history stores, image blobs and clipboard providers are internal to the
+/// crate and work with `io::Error`, and parsers such as the range parser have their
+/// own error types; both are converted into this one before leaving the crate.
+#[derive(Debug)]
+pub enum ZpError {
+    /// The clipboard couldn't be opened, read or written.
+    Clipboard(io::Error),
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// Arguments or input couldn't be parsed or selected from.
+    Parse(String),
+    /// The history file holds data that couldn't be read.
+    HistoryCorrupted(io::Error),
+    /// `~/.zp/config.json` or the sync configuration is invalid.
+    Config(String),
+    /// Talking to a sync peer failed.
+    Sync(String),
+    /// Encrypted data couldn't be encrypted or decrypted, e.g. with the wrong key.
+    Crypto(String),
+}
+
+impl ZpError {
+    /// Wraps an error from a history store, telling corrupted data apart from IO failures.
+    pub fn history(error: io::Error) -> Self {
+        if is_corrupted(&error) {
+            ZpError::HistoryCorrupted(error)
+        } else {
+            ZpError::Io(error)
+        }
+    }
+}
+
+impl fmt::Display for ZpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ZpError::Clipboard(e) => write!(f, "clipboard error: {}", e),
+            ZpError::Io(e) => write!(f, "{}", e),
+            ZpError::Parse(message) => f.write_str(message),
+            ZpError::HistoryCorrupted(e) => write!(f, "clipboard history is corrupted: {}", e),
+            ZpError::Config(message) => write!(f, "invalid configuration: {}", message),
+            ZpError::Sync(message) => write!(f, "sync failed: {}", message),
+            ZpError::Crypto(message) => write!(f, "encryption error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ZpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ZpError::Clipboard(e) | ZpError::Io(e) | ZpError::HistoryCorrupted(e) => Some(e),
+            ZpError::Parse(_) | ZpError::Config(_) | ZpError::Sync(_) | ZpError::Crypto(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ZpError {
+    fn from(error: io::Error) -> Self {
+        ZpError::Io(error)
+    }
+}
+
+impl From<RangeError> for ZpError {
+    fn from(error: RangeError) -> Self {
+        ZpError::Parse(error.to_string())
+    }
+}
+
+impl From<SelectError> for ZpError {
+    fn from(error: SelectError) -> Self {
+        ZpError::Parse(error.to_string())
+    }
+}
+
+impl From<DecodeError> for ZpError {
+    fn from(error: DecodeError) -> Self {
+        ZpError::Parse(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ZpError {
+    fn from(error: serde_json::Error) -> Self {
+        ZpError::Parse(error.to_string())
+    }
+}
+
+impl From<reqwest::Error> for ZpError {
+    fn from(error: reqwest::Error) -> Self {
+        ZpError::Sync(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_errors() {
+        let corrupted = io::Error::new(io::ErrorKind::InvalidData, "bad json");
+        assert!(matches!(
+            ZpError::history(corrupted),
+            ZpError::HistoryCorrupted(_)
+        ));
+
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+        let error = ZpError::history(denied);
+        assert!(matches!(error, ZpError::Io(_)));
+        assert_eq!(error.to_string(), "denied");
+    }
+}
@@ -1,9 +1,59 @@
-use std::fs::File;
-use std::io::Read;
-
-pub fn read_file_content(file_path: &str) -> Result<String, std::io::Error> {
-    let mut file = File::open(file_path)?;
-    let mut data = String::new();
-    file.read_to_string(&mut data)?;
-    Ok(data)
+use crate::blob::decode_image;
+use crate::encoding::{bytes_to_text, BinaryFormat, TextEncoding};
+use crate::error::ZpError;
+use arboard::ImageData;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Contents of a file to copy.
+pub enum FileContent {
+    Text(String),
+    Image(ImageData<'static>),
+}
+
+/// Reads `file_path` as an image if it is a PNG, JPEG or BMP file, and as text otherwise.
+///
+/// With an explicit `encoding` or `binary` format the file is always copied as text.
+pub fn read_file(
+    file_path: &str,
+    encoding: Option<TextEncoding>,
+    binary: Option<BinaryFormat>,
+) -> Result<FileContent, ZpError> {
+    let data = fs::read(file_path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", file_path, e)))?;
+    if encoding.is_none() && binary.is_none() {
+        if let Some(image) = decode_image(&data) {
+            return Ok(image.map(FileContent::Image)?);
+        }
+    }
+
+    bytes_to_text(&data, encoding, binary)
+        .map(FileContent::Text)
+        .map_err(|e| ZpError::Parse(format!("{}: {}", file_path, e)))
+}
+
+/// Expands glob patterns such as `src/*.rs` among `sources`, for shells that don't.
+///
+/// Sources that exist or contain no glob characters are kept as they are.
+pub fn expand_globs(sources: &[String]) -> Result<Vec<String>, ZpError> {
+    let mut expanded = vec![];
+    for source in sources {
+        if !source.contains(['*', '?', '[']) || Path::new(source).exists() {
+            expanded.push(source.clone());
+            continue;
+        }
+
+        let paths = glob::glob(source)
+            .map_err(|e| ZpError::Parse(format!("{}: {}", source, e)))?
+            .filter_map(Result::ok)
+            .filter(|path| path.is_file())
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return Err(ZpError::Parse(format!("no files match '{}'", source)));
+        }
+        expanded.extend(paths);
+    }
+    Ok(expanded)
 }
@@ -1,124 +1,467 @@
-use arboard::Clipboard;
-use chrono::{DateTime, Local, TimeZone};
-use ratatui::layout::Rect;
+use crate::blob::{blobs_dir, remove_blobs_of, remove_unreferenced_blobs, store_image};
+use crate::config::{
+    zp_dir, DedupStrategy, HistoryBackend, HistoryEncryption, RetentionPolicy, SyncConfig, ZpConfig,
+};
+use crate::dedup::{add_entry, bump_entry, content_hash, find_duplicate};
+use crate::ephemeral;
+use crate::error::ZpError;
+use crate::retention::{apply_retention, is_retained};
+use crate::secrets::protect_entry;
+use crate::store::{
+    is_corrupted, load_or_recover, open_configured_store, open_default_store, HistoryLock,
+    HistoryStore,
+};
+use arboard::ImageData;
+use chrono::{DateTime, Duration, Local, TimeZone};
 use serde::{Deserialize, Serialize};
-use std::env;
-use std::fs::{self};
-use std::io::{self, stdout};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
 use std::path::PathBuf;
+use uuid::Uuid;
 
-use crossterm::{
-    event::{self, KeyCode, KeyEvent},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
-use ratatui::text::{Line, Span};
-use ratatui::{
-    backend::CrosstermBackend,
-    style::{Color, Modifier, Style},
-    widgets::{Block, Borders, List, ListItem},
-    Terminal,
-};
+pub use crate::tui::print_clipboard_history;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ClipboardHistoryEntry {
+    /// Stable identifier, shared by every peer holding a copy of this entry.
+    #[serde(default)]
+    pub id: String,
     pub content: String,
     pub timestamp: String,
+    /// Peer that originally recorded the entry, if sync has been configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_id: Option<String>,
+    #[serde(default)]
+    pub source: EntrySource,
+    /// Files the content was copied from.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<SourceRef>,
+    /// Size of `content` in bytes.
+    #[serde(default)]
+    pub size: usize,
+    #[serde(default = "default_mime_type")]
+    pub mime_type: String,
+    /// Pinned entries are listed first and never pruned.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Hash of the content, the hex-encoded SHA-256.
+    #[serde(default)]
+    pub hash: String,
+    /// How many times this content was copied, when repeats are deduplicated.
+    #[serde(default = "default_use_count")]
+    pub use_count: u32,
+    /// Width and height of image entries, whose data is stored as a blob.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<(usize, usize)>,
+    /// The original content, encrypted with the local secrets key, when secrets in
+    /// it were redacted from `content` (see [`crate::secrets`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted: Option<String>,
+    /// When the entry is removed from the history, for copies made with `--expire`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+/// How an entry made its way into the history.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntrySource {
+    /// Recorded before sources were tracked.
+    #[default]
+    Unknown,
+    /// Copied with the `zp` command.
+    Manual,
+    /// Captured by the clipboard monitoring daemon.
+    Daemon,
+    /// Imported from a sync peer.
+    Sync,
+}
+
+/// A file an entry was copied from, with the 1-based inclusive line range when known.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SourceRef {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines: Option<(usize, usize)>,
+}
+
+fn default_mime_type() -> String {
+    "text/plain".to_string()
 }
 
-pub fn save_clipboard_history(content: String) {
-    // Try to get home directory, fallback to current directory
-    // Get the home directory from the HOME env var (works on Linux/macOS)
-    let history_dir = env::var("HOME")
-        .map(|home| PathBuf::from(home).join(".zp"))
-        .unwrap_or_else(|_| {
-            println!("Warning: HOME not set. Using current directory.");
-            PathBuf::from(".zp")
-        });
+fn default_use_count() -> u32 {
+    1
+}
 
-    // Create the directory if it doesn't exist
-    if !history_dir.exists() {
-        fs::create_dir_all(&history_dir).expect("Failed to create .zp directory");
+impl ClipboardHistoryEntry {
+    pub fn new(content: String, source: EntrySource) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            size: content.len(),
+            hash: content_hash(content.as_bytes()),
+            content,
+            timestamp: Local::now().to_rfc3339(),
+            peer_id: SyncConfig::local_peer_id(),
+            source,
+            sources: vec![],
+            mime_type: default_mime_type(),
+            pinned: false,
+            use_count: 1,
+            dimensions: None,
+            encrypted: None,
+            expires_at: None,
+        }
     }
-    let history_file = history_dir.join("clipboard_history.json");
-    let timestamp = Local::now().to_rfc3339();
 
-    let entry = ClipboardHistoryEntry { content, timestamp };
+    /// Returns true for image entries, whose `content` is only a label and whose
+    /// data lives in a blob in `~/.zp/blobs`.
+    pub fn is_image(&self) -> bool {
+        self.mime_type.starts_with("image/")
+    }
 
-    // Load existing history
-    let mut history = if let Ok(content) = fs::read_to_string(&history_file) {
-        serde_json::from_str::<Vec<ClipboardHistoryEntry>>(&content).unwrap_or_else(|_| vec![])
-    } else {
-        vec![]
+    pub fn with_sources(mut self, sources: Vec<SourceRef>) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    /// Makes the entry expire `ttl` from now.
+    pub fn expiring_in(mut self, ttl: Duration) -> Result<Self, ZpError> {
+        let expires_at = Local::now()
+            .checked_add_signed(ttl)
+            .ok_or_else(|| ZpError::Parse(format!("an expiry of {} is too long", ttl)))?;
+        self.expires_at = Some(expires_at.to_rfc3339());
+        Ok(self)
+    }
+
+    /// Returns true once an entry copied with `--expire` has outlived its time.
+    pub fn is_expired(&self, now: DateTime<Local>) -> bool {
+        self.expires_at
+            .as_deref()
+            .is_some_and(|expires_at| parse_entry_time(expires_at) <= now)
+    }
+
+    /// Fills in metadata missing from entries written by older versions.
+    ///
+    /// The id is derived from the timestamp and content, so every peer assigns the
+    /// same id to the same legacy entry.
+    pub fn normalize(&mut self) {
+        if self.id.is_empty() {
+            let name = format!("{}\n{}", self.timestamp, self.content);
+            self.id = Uuid::new_v5(&Uuid::NAMESPACE_OID, name.as_bytes()).to_string();
+        }
+        if self.size == 0 {
+            self.size = self.content.len();
+        }
+        if self.hash.is_empty() {
+            self.hash = content_hash(self.content.as_bytes());
+        }
+    }
+}
+
+/// Records `entry` in the history, folding it into an earlier entry when
+/// deduplication is configured, and applies the retention policy.
+///
+/// Secrets in the entry are handled first, as configured in `secrets`. This covers
+/// copies made with `zp` and those captured by the daemon alike.
+pub fn save_clipboard_history(entry: ClipboardHistoryEntry) -> Result<(), ZpError> {
+    let ZpConfig {
+        history: config,
+        secrets,
+        ..
+    } = ZpConfig::load()?;
+    let Some(entry) = protect_entry(entry, &secrets)? else {
+        eprintln!("Not saved to history: entry looks like it contains a secret");
+        return Ok(());
     };
+    let policy = config.retention;
+    if !is_retained(&entry, &policy, Local::now()) {
+        eprintln!("Not saved to history: entry is larger than history.retention.max_entry_bytes");
+        return Ok(());
+    }
 
-    // Add new entry
-    history.push(entry);
+    let _lock = HistoryLock::exclusive(&zp_dir())?;
+    let mut store = open_default_store()?;
 
-    // Serialize and write updated history
-    let serialized_history =
-        serde_json::to_string_pretty(&history).expect("Failed to serialize clipboard history");
-    fs::write(&history_file, serialized_history).expect("Failed to write clipboard history");
+    // The daemon also sees what `zp --expire` copies, which must not be recorded
+    // again without the expiry
+    if entry.source == EntrySource::Daemon && ephemeral::is_live_copy(&zp_dir(), &entry) {
+        return Ok(());
+    }
+    ephemeral::record(&zp_dir(), &entry)?;
+
+    // Repeats are folded into the existing entry, which means rewriting the history
+    // instead of appending to it
+    let duplicate = match config.dedup {
+        DedupStrategy::None => None,
+        strategy => {
+            let history = load_normalized(store.as_ref())?;
+            find_duplicate(&history, &entry, strategy).map(|position| (history, position))
+        }
+    };
+
+    if let Some((mut history, position)) = duplicate {
+        bump_entry(&mut history, position, entry.timestamp);
+        store.replace(&history)?;
+    } else if let Err(e) = store.append(&entry) {
+        if !is_corrupted(&e) {
+            return Err(e.into());
+        }
+
+        let mut history = load_normalized(store.as_ref())?;
+        add_entry(&mut history, entry, config.dedup);
+        store.replace(&history)?;
+    }
+
+    if policy.prunes() {
+        prune_store(store.as_mut(), &policy)?;
+    }
+    Ok(())
 }
 
-pub fn load_clipboard_history() -> Result<Vec<ClipboardHistoryEntry>, io::Error> {
-    let history_dir = env::var("HOME")
-        .map(|home| PathBuf::from(home).join(".zp"))
-        .unwrap_or_else(|_| {
-            println!("Warning: HOME not set. Using current directory.");
-            PathBuf::from(".zp")
-        });
+/// Stores `image` as a blob and records it in the history, along with the `sources`
+/// it was read from.
+///
+/// Blobs are plain PNG files, so images aren't recorded while the history is
+/// encrypted, where they would be the only content left readable.
+pub fn save_image_history(
+    image: &ImageData,
+    source: EntrySource,
+    sources: Vec<SourceRef>,
+) -> Result<(), ZpError> {
+    if ZpConfig::load()?.history.encryption != HistoryEncryption::None {
+        eprintln!("Not saved to history: images aren't recorded in an encrypted history");
+        return Ok(());
+    }
+    let entry = store_image(&blobs_dir(), image, source)?;
+    save_clipboard_history(entry.with_sources(sources))
+}
 
-    let history_file = history_dir.join("clipboard_history.json");
+/// Applies the configured retention policy to the stored history and removes
+/// expired entries.
+///
+/// Returns the number of entries removed.
+pub fn prune_clipboard_history() -> Result<usize, ZpError> {
+    let policy = ZpConfig::load()?.history.retention;
+    let _lock = HistoryLock::exclusive(&zp_dir())?;
+    ephemeral::forget_expired(&zp_dir())?;
+    prune_store(open_default_store()?.as_mut(), &policy)
+}
 
-    // Ensure the history directory exists
-    if fs::metadata(history_dir).is_err() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "History directory not found",
-        ));
+/// Prunes `store` with `policy`, only rewriting it when something was removed, and
+/// deletes the image blobs no entry refers to any more. The caller must hold the
+/// history lock.
+///
+/// Blobs are only collected here and by [`remove_from_clipboard_history`], not on
+/// every rewrite, so an entry deleted in the viewer can still be restored with its
+/// image.
+fn prune_store(store: &mut dyn HistoryStore, policy: &RetentionPolicy) -> Result<usize, ZpError> {
+    let mut history = load_or_recover(store).map_err(ZpError::history)?;
+    let removed = apply_retention(&mut history, policy, Local::now());
+    if removed > 0 {
+        store.replace(&history)?;
     }
+    remove_orphaned_blobs(&history);
+    Ok(removed)
+}
+
+pub fn load_clipboard_history() -> Result<Vec<ClipboardHistoryEntry>, ZpError> {
+    let result = {
+        let _lock = HistoryLock::shared(&zp_dir())?;
+        open_default_store()?.load()
+    };
+
+    let mut history = match result {
+        // Back up the damaged file and rewrite it with whatever could be salvaged
+        Err(e) if is_corrupted(&e) => update_clipboard_history(|history| history.clone())?,
+        result => result.map_err(ZpError::history)?,
+    };
+    let now = Local::now();
+    history.retain(|entry| !entry.is_expired(now));
+    history
+        .iter_mut()
+        .for_each(ClipboardHistoryEntry::normalize);
+    Ok(history)
+}
 
-    // Read the contents of the history file
-    let content = fs::read_to_string(&history_file)?;
+/// Runs a locked read-modify-write cycle over the stored history.
+///
+/// `f` receives the current entries (oldest first) and whatever it leaves in the
+/// vector is written back atomically. Concurrent writers from the daemon, the sync
+/// server and the CLI wait for each other instead of overwriting each other's changes.
+pub fn update_clipboard_history<F, R>(f: F) -> Result<R, ZpError>
+where
+    F: FnOnce(&mut Vec<ClipboardHistoryEntry>) -> R,
+{
+    let _lock = HistoryLock::exclusive(&zp_dir())?;
+    let mut store = open_default_store()?;
+    let mut history = load_normalized(store.as_ref())?;
+    let result = f(&mut history);
+    store.replace(&history)?;
+    Ok(result)
+}
 
-    // Deserialize the JSON content into a vector of ClipboardHistoryEntry
-    if content.trim() == "[]" {
-        return Ok(vec![]);
+/// Like [`update_clipboard_history`], for `f` removing entries: the images of the
+/// removed entries are deleted too, unless another entry still refers to them.
+///
+/// Nothing else is pruned, so no entry is removed that `f` didn't remove.
+pub fn remove_from_clipboard_history<F, R>(f: F) -> Result<R, ZpError>
+where
+    F: FnOnce(&mut Vec<ClipboardHistoryEntry>) -> R,
+{
+    let _lock = HistoryLock::exclusive(&zp_dir())?;
+    let mut store = open_default_store()?;
+    let mut history = load_normalized(store.as_ref())?;
+    let before = history.clone();
+    let result = f(&mut history);
+    store.replace(&history)?;
+
+    let kept: HashSet<&str> = history.iter().map(|entry| entry.id.as_str()).collect();
+    let removed: Vec<ClipboardHistoryEntry> = before
+        .into_iter()
+        .filter(|entry| !kept.contains(entry.id.as_str()))
+        .collect();
+    if let Err(e) = remove_blobs_of(&blobs_dir(), &removed, &history) {
+        eprintln!("Warning: failed to remove unused image blobs: {}", e);
     }
-    // Ensure the JSON is valid and not empty
-    let trimmed_content = content.trim();
-    if trimmed_content.is_empty() || !trimmed_content.starts_with('[') {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "Invalid or empty clipboard history file",
-        ));
+    Ok(result)
+}
+
+/// Deletes image blobs no longer referenced by `history`, only warning on failure
+/// since the history itself has already been written.
+fn remove_orphaned_blobs(history: &[ClipboardHistoryEntry]) {
+    if let Err(e) = remove_unreferenced_blobs(&blobs_dir(), history) {
+        eprintln!("Warning: failed to remove unused image blobs: {}", e);
     }
+}
 
-    Ok(serde_json::from_str(trimmed_content)?)
+/// Loads `store` with [`load_or_recover`], leaving out expired entries, and fills in
+/// metadata missing from old entries.
+fn load_normalized(store: &dyn HistoryStore) -> Result<Vec<ClipboardHistoryEntry>, ZpError> {
+    let mut history = load_or_recover(store).map_err(ZpError::history)?;
+    let now = Local::now();
+    history.retain(|entry| !entry.is_expired(now));
+    history
+        .iter_mut()
+        .for_each(ClipboardHistoryEntry::normalize);
+    Ok(history)
 }
 
-pub fn print_clipboard_history() -> Result<(), io::Error> {
-    let entries = load_clipboard_history().map_err(|e| {
-        eprintln!("Failed to load clipboard history: {}", e);
-        e
-    })?;
+/// Finds the position in `entries` (oldest first) of the entry referred to by `reference`.
+///
+/// A number is an index counting back from the most recent entry (`0` is the latest);
+/// anything else, or a number past the end of the history, is matched against entry
+/// ids, where a unique prefix is enough.
+pub fn resolve_entry(entries: &[ClipboardHistoryEntry], reference: &str) -> Result<usize, ZpError> {
+    if let Some(position) = reference
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| entries.len().checked_sub(index.checked_add(1)?))
+    {
+        return Ok(position);
+    }
+
+    let matches: Vec<usize> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.id.starts_with(reference))
+        .map(|(i, _)| i)
+        .collect();
+
+    match matches.as_slice() {
+        [position] => Ok(*position),
+        [] => Err(ZpError::Parse(format!(
+            "No history entry with index or id '{}'",
+            reference
+        ))),
+        _ => Err(ZpError::Parse(format!(
+            "Id prefix '{}' matches {} entries, use a longer prefix",
+            reference,
+            matches.len()
+        ))),
+    }
+}
+
+/// Inserts `entry` at its chronological position in `entries`.
+pub fn insert_by_timestamp(entries: &mut Vec<ClipboardHistoryEntry>, entry: ClipboardHistoryEntry) {
+    let time = parse_entry_time(&entry.timestamp);
+    let position = entries
+        .iter()
+        .position(|e| parse_entry_time(&e.timestamp) > time)
+        .unwrap_or(entries.len());
+    entries.insert(position, entry);
+}
 
-    enable_raw_mode()?;
-    execute!(stdout(), EnterAlternateScreen)?;
+/// Overwrites the stored history with `entries`.
+pub fn replace_clipboard_history(entries: &[ClipboardHistoryEntry]) -> Result<(), ZpError> {
+    let _lock = HistoryLock::exclusive(&zp_dir())?;
+    open_default_store()?.replace(entries)?;
+    Ok(())
+}
 
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
-    let result = run_app(&mut terminal, &entries);
+/// Moves the history into the store for `encryption`, e.g. from the backend's plain
+/// file into the encrypted one, and saves the choice in `~/.zp/config.json`.
+///
+/// The old file is removed once the history has been written. Returns the number
+/// of entries moved.
+pub fn set_history_encryption(
+    encryption: HistoryEncryption,
+    key_file: Option<PathBuf>,
+) -> Result<usize, ZpError> {
+    let mut config = ZpConfig::load()?;
+    let dir = zp_dir();
+    let _lock = HistoryLock::exclusive(&dir)?;
+
+    let (history, old_path) = {
+        let old = open_configured_store(&dir, &config.history)?;
+        (load_normalized(old.as_ref())?, old.path().to_path_buf())
+    };
+    check_encryptable(&history, encryption)?;
+
+    config.history.encryption = encryption;
+    config.history.key_file = key_file;
+    let mut new = open_configured_store(&dir, &config.history)?;
+    new.replace(&history)?;
+    config.save()?;
+
+    let mut stale = vec![old_path];
+    if encryption != HistoryEncryption::None {
+        // Other backends import the legacy JSON file once and leave it behind
+        stale.push(dir.join(HistoryBackend::Json.file_name()));
+    }
+    for path in stale.iter().filter(|path| path.as_path() != new.path()) {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(history.len())
+}
 
-    disable_raw_mode()?;
-    execute!(stdout(), LeaveAlternateScreen)?;
-    result
+/// Refuses to encrypt a history holding images, whose blobs would stay readable.
+fn check_encryptable(
+    history: &[ClipboardHistoryEntry],
+    encryption: HistoryEncryption,
+) -> Result<(), ZpError> {
+    let images = history.iter().filter(|entry| entry.is_image()).count();
+    if encryption != HistoryEncryption::None && images > 0 {
+        return Err(ZpError::Parse(format!(
+            "The history has {} image entries, which can't be encrypted. Delete them with `zp history rm` first",
+            images
+        )));
+    }
+    Ok(())
 }
 
-fn format_elapsed_time(timestamp: &str) -> String {
-    let entry_time = DateTime::parse_from_rfc3339(timestamp)
+/// Parses an entry timestamp, treating unreadable ones as the Unix epoch.
+pub(crate) fn parse_entry_time(timestamp: &str) -> DateTime<Local> {
+    DateTime::parse_from_rfc3339(timestamp)
         .map(|dt| dt.with_timezone(&Local))
-        .unwrap_or_else(|_| Local.timestamp_opt(0, 0).unwrap());
+        .unwrap_or_else(|_| Local.timestamp_opt(0, 0).unwrap())
+}
+
+pub(crate) fn format_elapsed_time(timestamp: &str) -> String {
+    let entry_time = parse_entry_time(timestamp);
     let now = Local::now();
     let duration = now.signed_duration_since(entry_time);
 
@@ -135,108 +478,74 @@ fn format_elapsed_time(timestamp: &str) -> String {
     format!("{:>7}", formatted) // Right-align with 7 characters
 }
 
-fn run_app(
-    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
-    entries: &[ClipboardHistoryEntry],
-) -> io::Result<()> {
-    let mut clipboard = Clipboard::new().unwrap();
-    let mut selected = entries.len().saturating_sub(1); // Start at the bottom
-    let mut offset = 0; // Offset to manage scrolling
-
-    loop {
-        terminal.draw(|f| {
-            let size = f.area();
-            let app_height = size.height / 2;
-            let max_visible_items = app_height.saturating_sub(2) as usize; // Account for borders
-
-            // Ensure offset keeps the selected item in view
-            if selected >= offset + max_visible_items {
-                offset = selected.saturating_sub(max_visible_items).saturating_add(1);
-            } else if selected < offset {
-                offset = selected;
-            }
-
-            // Calculate the visible entries
-            let visible_entries = &entries[offset..entries.len().min(offset + max_visible_items)];
-
-            // Render list items
-            let items: Vec<ListItem> = visible_entries
-                .iter()
-                .enumerate()
-                .map(|(i, entry)| {
-                    let actual_index = offset + i;
-                    let elapsed = format_elapsed_time(&entry.timestamp);
-                    let elapsed_styled = Span::styled(elapsed, Style::default().fg(Color::Green));
-                    let content_styled =
-                        Span::styled(entry.content.clone(), Style::default().fg(Color::White));
-
-                    let highlight_symbol = if actual_index == selected {
-                        Span::styled(
-                            "> ",
-                            Style::default()
-                                .fg(Color::Green)
-                                .add_modifier(Modifier::BOLD),
-                        )
-                    } else {
-                        Span::raw("  ")
-                    };
-
-                    let line = Line::from(vec![
-                        highlight_symbol,
-                        elapsed_styled,
-                        Span::raw(" "),
-                        content_styled,
-                    ]);
-
-                    if actual_index == selected {
-                        ListItem::new(line).style(Style::default().bg(Color::DarkGray))
-                    } else {
-                        ListItem::new(line)
-                    }
-                })
-                .collect();
-
-            let list = List::new(items).block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(" zp ")
-                    .style(Style::default().bg(Color::Black).fg(Color::White)),
-            );
-
-            f.render_widget(
-                list,
-                Rect {
-                    y: size.height - app_height,
-                    width: size.width,
-                    height: app_height,
-                    ..size
-                },
-            );
-        })?;
-
-        // Handle input
-        if let event::Event::Key(KeyEvent { code, .. }) = event::read()? {
-            match code {
-                KeyCode::Up => {
-                    if selected > 0 {
-                        selected = selected.saturating_sub(1);
-                    }
-                }
-                KeyCode::Down => {
-                    if selected < entries.len().saturating_sub(1) {
-                        selected += 1;
-                    }
-                }
-                KeyCode::Enter => {
-                    let content = &entries[selected].content;
-                    clipboard.set_text(content.to_owned()).unwrap();
-                    println!("Copied: {}", content);
-                    break;
-                }
-                KeyCode::Esc => break,
-                _ => {}
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_entry_deserializes_with_defaults() {
+        let json = r#"{"content": "hello", "timestamp": "2024-01-01T00:00:00+00:00"}"#;
+        let mut entry: ClipboardHistoryEntry = serde_json::from_str(json).unwrap();
+        assert_eq!(entry.source, EntrySource::Unknown);
+        assert_eq!(entry.mime_type, "text/plain");
+        assert!(entry.sources.is_empty());
+
+        entry.normalize();
+        assert_eq!(entry.size, 5);
+
+        // Legacy entries get the same id wherever they are loaded
+        let mut again: ClipboardHistoryEntry = serde_json::from_str(json).unwrap();
+        again.normalize();
+        assert!(!entry.id.is_empty());
+        assert_eq!(entry.id, again.id);
+    }
+
+    #[test]
+    fn test_images_block_encryption() {
+        let mut history = vec![ClipboardHistoryEntry::new(
+            "text".to_string(),
+            EntrySource::Manual,
+        )];
+        assert!(check_encryptable(&history, HistoryEncryption::Keyfile).is_ok());
+
+        let mut image = ClipboardHistoryEntry::new("[image 2x2]".to_string(), EntrySource::Manual);
+        image.mime_type = crate::blob::IMAGE_MIME_TYPE.to_string();
+        history.push(image);
+        assert!(check_encryptable(&history, HistoryEncryption::Keyfile).is_err());
+        assert!(check_encryptable(&history, HistoryEncryption::Passphrase).is_err());
+        // Decrypting is always allowed
+        assert!(check_encryptable(&history, HistoryEncryption::None).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_entry() {
+        let entries: Vec<_> = ["a1b2", "a1c3", "3f00"]
+            .iter()
+            .map(|id| {
+                let mut entry = ClipboardHistoryEntry::new(id.to_string(), EntrySource::Manual);
+                entry.id = id.to_string();
+                entry
+            })
+            .collect();
+
+        assert_eq!(resolve_entry(&entries, "0").ok(), Some(2));
+        assert_eq!(resolve_entry(&entries, "2").ok(), Some(0));
+        // Numbers past the end of the history are tried as id prefixes
+        assert_eq!(resolve_entry(&entries, "3").ok(), Some(2));
+        assert!(resolve_entry(&entries, "4").is_err());
+        assert_eq!(resolve_entry(&entries, "a1c").ok(), Some(1));
+        assert!(resolve_entry(&entries, "a1").is_err());
+        assert!(resolve_entry(&entries, "not-an-id").is_err());
+    }
+
+    #[test]
+    fn test_new_entry_metadata() {
+        let entry = ClipboardHistoryEntry::new("héllo".to_string(), EntrySource::Daemon);
+        assert_eq!(entry.size, 6);
+        assert_eq!(entry.source, EntrySource::Daemon);
+        assert_ne!(
+            entry.id,
+            ClipboardHistoryEntry::new("héllo".to_string(), EntrySource::Daemon).id
+        );
     }
-    Ok(())
 }
@@ -1,12 +1,33 @@
+mod blob;
 mod clipboard;
+mod commands;
+mod concat;
 pub mod config;
+mod crypto;
 pub mod daemon;
+mod dedup;
+pub mod duration;
+mod encoding;
+mod ephemeral;
+pub mod error;
 mod file;
 pub mod history;
+mod provider;
 mod query;
+mod range;
+mod retention;
 mod run;
+pub mod secrets;
+mod select;
+mod store;
 pub mod sync;
+mod transform;
+mod tui;
 
+pub use clipboard::clear_after;
+pub use commands::history::run_history_command;
+pub use commands::paste::run_paste_command;
 pub use daemon::{daemon_status, run_daemon_worker, start_daemon, stop_daemon};
-pub use query::{Query, Zp};
+pub use error::ZpError;
+pub use query::{Command, HistoryCommand, Query, Zp};
 pub use run::run;
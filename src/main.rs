@@ -3,7 +3,9 @@ use std::env;
 use std::process;
 use zp::history::print_clipboard_history;
 use zp::sync::handler::SyncHandler;
-use zp::{daemon_status, start_daemon, stop_daemon, Query, Zp};
+use zp::{
+    daemon_status, run_history_command, run_paste_command, start_daemon, stop_daemon, Command, Zp,
+};
 
 #[tokio::main]
 async fn main() {
@@ -19,6 +21,33 @@ async fn main() {
         return;
     }
 
+    // Hidden flag for the process that clears the clipboard after --expire
+    if let Some(seconds) = zp.clear_after {
+        if let Err(e) = zp::clear_after(seconds) {
+            eprintln!("Failed to clear the clipboard: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(command) = &zp.command {
+        match command {
+            Command::History(history_command) => {
+                if let Err(e) = run_history_command(history_command) {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            }
+            Command::Paste { entry, to, append } => {
+                if let Err(e) = run_paste_command(entry.as_deref(), to.as_deref(), *append) {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        return;
+    }
+
     // Check daemon commands first
     if zp.daemon {
         if let Err(e) = start_daemon() {
@@ -97,21 +126,13 @@ async fn main() {
 
     // Original logic for logs and other commands
     if zp.logs {
-        print_clipboard_history().unwrap();
-    } else {
-        match Query::build(&zp) {
-            Ok(_) => {
-                // Run the application with the Zp struct (passing the original parsed Zp)
-                if let Err(e) = zp::run(zp) {
-                    eprintln!("Application error: {e}");
-                    process::exit(1);
-                }
-            }
-            Err(err) => {
-                eprintln!("Problem parsing query: {}", err);
-                process::exit(1);
-            }
+        if let Err(e) = print_clipboard_history(zp.print, &zp.transform) {
+            eprintln!("Failed to show clipboard history: {}", e);
+            process::exit(1);
         }
+    } else if let Err(e) = zp::run(zp) {
+        eprintln!("Application error: {e}");
+        process::exit(1);
     }
 }
 
@@ -0,0 +1,146 @@
+use crate::provider::focus::focused_application;
+use crate::provider::ClipboardProvider;
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Lists the targets, i.e. MIME types, of the X11 clipboard selection.
+pub(crate) const XCLIP_TARGETS: &[&str] = &[
+    "xclip",
+    "-selection",
+    "clipboard",
+    "-out",
+    "-target",
+    "TARGETS",
+];
+
+/// Copies and pastes by running external tools such as wl-copy, xclip or tmux.
+pub struct CommandProvider {
+    name: &'static str,
+    copy: &'static [&'static str],
+    paste: &'static [&'static str],
+    /// Lists the MIME types on offer, one per line; empty if the tool can't.
+    list_types: &'static [&'static str],
+    /// Whether the tool works on X11, where the focused window can be looked up.
+    x11: bool,
+}
+
+impl CommandProvider {
+    pub fn wl_clipboard() -> Self {
+        Self {
+            name: "wl-clipboard",
+            copy: &["wl-copy"],
+            paste: &["wl-paste", "--no-newline"],
+            list_types: &["wl-paste", "--list-types"],
+            x11: false,
+        }
+    }
+
+    pub fn xclip() -> Self {
+        Self {
+            name: "xclip",
+            copy: &["xclip", "-selection", "clipboard", "-in"],
+            paste: &["xclip", "-selection", "clipboard", "-out"],
+            list_types: XCLIP_TARGETS,
+            x11: true,
+        }
+    }
+
+    /// tmux paste buffers; `-w` (tmux 3.2 and later) also sets the outer terminal's
+    /// clipboard when tmux's `set-clipboard` option allows it.
+    pub fn tmux() -> Self {
+        Self {
+            name: "tmux",
+            copy: &["tmux", "load-buffer", "-w", "-"],
+            paste: &["tmux", "save-buffer", "-"],
+            list_types: &[],
+            x11: false,
+        }
+    }
+
+    /// Returns the provider if its copy command is installed.
+    pub fn check(self) -> io::Result<Self> {
+        if is_installed(self.copy[0]) {
+            Ok(self)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not installed", self.copy[0]),
+            ))
+        }
+    }
+}
+
+impl ClipboardProvider for CommandProvider {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn get_text(&mut self) -> io::Result<Option<String>> {
+        run_output(self.paste)
+    }
+
+    fn mime_types(&mut self) -> io::Result<Vec<String>> {
+        if self.list_types.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(run_output(self.list_types)?
+            .map(|types| types.lines().map(str::to_string).collect())
+            .unwrap_or_default())
+    }
+
+    fn source_application(&mut self) -> io::Result<Option<String>> {
+        Ok(if self.x11 {
+            focused_application()
+        } else {
+            None
+        })
+    }
+
+    fn set_text(&mut self, text: &str) -> io::Result<()> {
+        // wl-copy and xclip fork to serve the selection, so don't wait on their stdout
+        let mut child = Command::new(self.copy[0])
+            .args(&self.copy[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(text.as_bytes())?;
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "{} exited with {}",
+                self.copy[0], status
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Runs `args` and returns its output, or `None` if it fails or the output isn't text.
+///
+/// These tools fail when there is nothing to paste, so failing isn't an error.
+pub(crate) fn run_output(args: &[&str]) -> io::Result<Option<String>> {
+    let output = Command::new(args[0])
+        .args(&args[1..])
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(output.stdout).ok())
+}
+
+/// Whether `program` is an executable on `PATH`.
+pub fn is_installed(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|path| {
+        env::split_paths(&path).any(|dir| {
+            let candidate = dir.join(program);
+            candidate.is_file() || candidate.with_extension("exe").is_file()
+        })
+    })
+}
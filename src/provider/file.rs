@@ -0,0 +1,63 @@
+use crate::blob::{decode_image, encode_png};
+use crate::provider::ClipboardProvider;
+use crate::store::write_atomic;
+use arboard::ImageData;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// A clipboard kept in a plain file, for tests and machines without any other
+/// clipboard. Images are stored as PNG.
+pub struct FileProvider {
+    path: PathBuf,
+}
+
+impl FileProvider {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn read(&self) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&self, bytes: &[u8]) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Copied passwords end up here, so the file is kept private like the history
+        write_atomic(&self.path, bytes)
+    }
+}
+
+impl ClipboardProvider for FileProvider {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn get_text(&mut self) -> io::Result<Option<String>> {
+        Ok(self
+            .read()?
+            .filter(|bytes| decode_image(bytes).is_none())
+            .and_then(|bytes| String::from_utf8(bytes).ok()))
+    }
+
+    fn set_text(&mut self, text: &str) -> io::Result<()> {
+        self.write(text.as_bytes())
+    }
+
+    fn get_image(&mut self) -> io::Result<Option<ImageData<'static>>> {
+        match self.read()? {
+            Some(bytes) => decode_image(&bytes).transpose(),
+            None => Ok(None),
+        }
+    }
+
+    fn set_image(&mut self, image: ImageData) -> io::Result<()> {
+        self.write(&encode_png(&image)?)
+    }
+}
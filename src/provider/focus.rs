@@ -0,0 +1,24 @@
+use crate::provider::command::{is_installed, run_output};
+use std::env;
+
+/// Name or window class of the focused application, taken as the source of a
+/// clipboard change since clipboards don't record who wrote to them.
+///
+/// Looked up with `osascript` on macOS and `xdotool` on X11; elsewhere, including
+/// Wayland, the focused application isn't exposed.
+pub fn focused_application() -> Option<String> {
+    let args: &[&str] = if cfg!(target_os = "macos") {
+        &[
+            "osascript",
+            "-e",
+            "tell application \"System Events\" to get name of first application process whose frontmost is true",
+        ]
+    } else if env::var_os("DISPLAY").is_some() && is_installed("xdotool") {
+        &["xdotool", "getactivewindow", "getwindowclassname"]
+    } else {
+        return None;
+    };
+
+    let name = run_output(args).ok()??.trim().to_string();
+    (!name.is_empty()).then_some(name)
+}
@@ -0,0 +1,247 @@
+pub mod command;
+pub mod file;
+pub mod focus;
+pub mod osc52;
+pub mod system;
+
+use crate::config::{zp_dir, ClipboardConfig, ClipboardProviderKind, ZpConfig};
+use crate::error::ZpError;
+use arboard::ImageData;
+use is_terminal::IsTerminal;
+use std::env;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Once;
+
+pub use command::CommandProvider;
+pub use file::FileProvider;
+pub use osc52::Osc52Provider;
+pub use system::SystemProvider;
+
+/// A clipboard zp copies to and reads from.
+pub trait ClipboardProvider {
+    /// Name of the provider, as written in the `clipboard.provider` setting.
+    fn name(&self) -> &'static str;
+
+    /// Reads the clipboard text, or `None` when the clipboard holds no text.
+    fn get_text(&mut self) -> io::Result<Option<String>>;
+
+    fn set_text(&mut self, text: &str) -> io::Result<()>;
+
+    /// Reads the clipboard image, or `None` when the clipboard holds no image.
+    fn get_image(&mut self) -> io::Result<Option<ImageData<'static>>> {
+        Ok(None)
+    }
+
+    fn set_image(&mut self, _image: ImageData) -> io::Result<()> {
+        Err(unsupported(format!(
+            "the {} clipboard can't hold images",
+            self.name()
+        )))
+    }
+
+    /// MIME types or format names the clipboard content is offered as, where the
+    /// platform exposes them. Password managers add hints such as
+    /// `x-kde-passwordManagerHint` here.
+    fn mime_types(&mut self) -> io::Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    /// Name or window class of the application the clipboard content came from,
+    /// where the platform exposes it.
+    fn source_application(&mut self) -> io::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+fn unsupported(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, message)
+}
+
+/// What the current session offers, used to pick a provider automatically.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Environment {
+    /// macOS or Windows, where the system clipboard is always there.
+    pub desktop: bool,
+    pub wayland: bool,
+    pub x11: bool,
+    pub tmux: bool,
+    /// A terminal that OSC 52 escape sequences can be written to.
+    pub terminal: bool,
+    pub wl_copy: bool,
+    pub xclip: bool,
+}
+
+impl Environment {
+    pub fn current() -> Self {
+        let set = |name| env::var_os(name).is_some_and(|value| !value.is_empty());
+        Self {
+            desktop: cfg!(any(target_os = "macos", target_os = "windows")),
+            wayland: set("WAYLAND_DISPLAY"),
+            x11: set("DISPLAY"),
+            tmux: set("TMUX"),
+            terminal: io::stderr().is_terminal()
+                || OpenOptions::new().write(true).open("/dev/tty").is_ok(),
+            wl_copy: command::is_installed("wl-copy"),
+            xclip: command::is_installed("xclip"),
+        }
+    }
+}
+
+/// Providers to try, in order, for `clipboard.provider = "auto"`.
+///
+/// Graphical sessions use the system clipboard, going through wl-clipboard on Wayland
+/// since arboard is built without Wayland support. Headless sessions, typically over
+/// SSH, use tmux buffers inside tmux and OSC 52 on a terminal. The file provider
+/// always works and comes last.
+pub fn candidates(env: &Environment) -> Vec<ClipboardProviderKind> {
+    let mut kinds = vec![];
+    if env.wayland && env.wl_copy {
+        kinds.push(ClipboardProviderKind::WlClipboard);
+    }
+    if env.desktop || env.x11 {
+        kinds.push(ClipboardProviderKind::System);
+    }
+    if env.x11 && env.xclip {
+        kinds.push(ClipboardProviderKind::Xclip);
+    }
+    if env.tmux {
+        kinds.push(ClipboardProviderKind::Tmux);
+    }
+    if env.terminal {
+        kinds.push(ClipboardProviderKind::Osc52);
+    }
+    kinds.push(ClipboardProviderKind::File);
+    kinds
+}
+
+/// Opens the provider selected in `~/.zp/config.json`.
+pub fn open_clipboard() -> Result<Box<dyn ClipboardProvider>, ZpError> {
+    let config = ZpConfig::load_or_default().clipboard;
+    open_provider(config.provider, &config).map_err(ZpError::Clipboard)
+}
+
+/// Opens the provider of the given kind, trying each of [`candidates`] for `Auto`.
+pub fn open_provider(
+    kind: ClipboardProviderKind,
+    config: &ClipboardConfig,
+) -> io::Result<Box<dyn ClipboardProvider>> {
+    let provider: Box<dyn ClipboardProvider> = match kind {
+        ClipboardProviderKind::Auto => {
+            let mut last_error = None;
+            for kind in candidates(&Environment::current()) {
+                match open_provider(kind, config) {
+                    Ok(provider) => {
+                        if kind == ClipboardProviderKind::File {
+                            warn_file_fallback(config);
+                        }
+                        return Ok(provider);
+                    }
+                    Err(e) => last_error = Some(e),
+                }
+            }
+            return Err(last_error.unwrap_or_else(|| unsupported("no clipboard found".into())));
+        }
+        ClipboardProviderKind::System => Box::new(SystemProvider::new()?),
+        ClipboardProviderKind::Osc52 => Box::new(Osc52Provider),
+        ClipboardProviderKind::WlClipboard => Box::new(CommandProvider::wl_clipboard().check()?),
+        ClipboardProviderKind::Xclip => Box::new(CommandProvider::xclip().check()?),
+        ClipboardProviderKind::Tmux => Box::new(CommandProvider::tmux().check()?),
+        ClipboardProviderKind::File => Box::new(FileProvider::new(clipboard_file(config))),
+    };
+    Ok(provider)
+}
+
+/// Tells the user, once per process, that copies go to a file rather than a clipboard.
+fn warn_file_fallback(config: &ClipboardConfig) {
+    static WARNED: Once = Once::new();
+    WARNED.call_once(|| {
+        eprintln!(
+            "zp: no clipboard found, copying to {} instead (set clipboard.provider to silence this)",
+            clipboard_file(config).display()
+        );
+    });
+}
+
+fn clipboard_file(config: &ClipboardConfig) -> PathBuf {
+    config
+        .file
+        .clone()
+        .unwrap_or_else(|| zp_dir().join("clipboard"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ClipboardProviderKind::*;
+
+    #[test]
+    fn test_candidates() {
+        let desktop = Environment {
+            desktop: true,
+            terminal: true,
+            ..Environment::default()
+        };
+        assert_eq!(candidates(&desktop), vec![System, Osc52, File]);
+
+        let wayland = Environment {
+            wayland: true,
+            x11: true,
+            wl_copy: true,
+            xclip: true,
+            ..Environment::default()
+        };
+        assert_eq!(candidates(&wayland), vec![WlClipboard, System, Xclip, File]);
+
+        // A headless server reached over SSH
+        let ssh = Environment {
+            tmux: true,
+            terminal: true,
+            ..Environment::default()
+        };
+        assert_eq!(candidates(&ssh), vec![Tmux, Osc52, File]);
+        assert_eq!(candidates(&Environment::default()), vec![File]);
+    }
+
+    #[test]
+    fn test_file_provider() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ClipboardConfig {
+            provider: File,
+            file: Some(dir.path().join("clipboard")),
+        };
+        let mut clipboard = open_provider(File, &config).unwrap();
+        assert_eq!(clipboard.get_text().unwrap(), None);
+
+        clipboard.set_text("copied").unwrap();
+        assert_eq!(clipboard.get_text().unwrap().as_deref(), Some("copied"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = std::fs::metadata(dir.path().join("clipboard")).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        }
+        assert!(clipboard.get_image().unwrap().is_none());
+
+        let image = ImageData {
+            width: 1,
+            height: 1,
+            bytes: vec![255, 0, 0, 255].into(),
+        };
+        clipboard.set_image(image).unwrap();
+        assert_eq!(clipboard.get_text().unwrap(), None);
+        let image = clipboard.get_image().unwrap().unwrap();
+        assert_eq!((image.width, image.height), (1, 1));
+    }
+
+    #[test]
+    fn test_osc52_sequence() {
+        assert_eq!(osc52::sequence("zp", false), "\x1b]52;c;enA=\x07");
+        // tmux needs the sequence wrapped to pass it on to the outer terminal
+        assert_eq!(
+            osc52::sequence("zp", true),
+            "\x1bPtmux;\x1b\x1b]52;c;enA=\x07\x1b\\"
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use crate::provider::{unsupported, ClipboardProvider};
+use base64::Engine;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+/// Copies by writing an OSC 52 escape sequence to the terminal, which sets the
+/// clipboard of the machine the terminal runs on. This works over SSH, but the
+/// clipboard can't be read back.
+pub struct Osc52Provider;
+
+/// The OSC 52 sequence that copies `text`, wrapped in a passthrough sequence for tmux.
+pub fn sequence(text: &str, tmux: bool) -> String {
+    let osc = format!(
+        "\x1b]52;c;{}\x07",
+        base64::engine::general_purpose::STANDARD.encode(text)
+    );
+    if tmux {
+        format!("\x1bPtmux;{}\x1b\\", osc.replace('\x1b', "\x1b\x1b"))
+    } else {
+        osc
+    }
+}
+
+impl ClipboardProvider for Osc52Provider {
+    fn name(&self) -> &'static str {
+        "osc52"
+    }
+
+    fn get_text(&mut self) -> io::Result<Option<String>> {
+        Err(unsupported("the osc52 clipboard can't be read".to_string()))
+    }
+
+    fn set_text(&mut self, text: &str) -> io::Result<()> {
+        let sequence = sequence(text, env::var_os("TMUX").is_some());
+        // Write to the terminal itself so stdout can still be redirected
+        match OpenOptions::new().write(true).open("/dev/tty") {
+            Ok(mut tty) => tty.write_all(sequence.as_bytes()),
+            Err(_) => io::stderr().write_all(sequence.as_bytes()),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::provider::command::{is_installed, run_output, XCLIP_TARGETS};
+use crate::provider::focus::focused_application;
+use crate::provider::ClipboardProvider;
+use arboard::{Clipboard, ImageData};
+use std::io;
+
+/// The desktop clipboard on macOS, Windows and X11, through arboard.
+pub struct SystemProvider {
+    clipboard: Clipboard,
+}
+
+impl SystemProvider {
+    pub fn new() -> io::Result<Self> {
+        let clipboard = Clipboard::new().map_err(io::Error::other)?;
+        Ok(Self { clipboard })
+    }
+}
+
+impl ClipboardProvider for SystemProvider {
+    fn name(&self) -> &'static str {
+        "system"
+    }
+
+    fn get_text(&mut self) -> io::Result<Option<String>> {
+        match self.clipboard.get_text() {
+            Ok(text) => Ok(Some(text)),
+            Err(arboard::Error::ContentNotAvailable) => Ok(None),
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+
+    fn set_text(&mut self, text: &str) -> io::Result<()> {
+        self.clipboard.set_text(text).map_err(io::Error::other)
+    }
+
+    fn get_image(&mut self) -> io::Result<Option<ImageData<'static>>> {
+        match self.clipboard.get_image() {
+            Ok(image) => Ok(Some(image)),
+            Err(arboard::Error::ContentNotAvailable) => Ok(None),
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+
+    fn set_image(&mut self, image: ImageData) -> io::Result<()> {
+        self.clipboard.set_image(image).map_err(io::Error::other)
+    }
+
+    /// arboard doesn't expose the formats on offer, so on X11 they are read with
+    /// xclip when it is installed.
+    fn mime_types(&mut self) -> io::Result<Vec<String>> {
+        if cfg!(any(target_os = "macos", target_os = "windows")) || !is_installed("xclip") {
+            return Ok(vec![]);
+        }
+        Ok(run_output(XCLIP_TARGETS)?
+            .map(|types| types.lines().map(str::to_string).collect())
+            .unwrap_or_default())
+    }
+
+    fn source_application(&mut self) -> io::Result<Option<String>> {
+        Ok(focused_application())
+    }
+}
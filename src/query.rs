@@ -1,21 +1,153 @@
-use clap::Parser;
+use crate::concat::{parse_size, HeaderStyle};
+use crate::duration::parse_duration;
+use crate::encoding::{bytes_to_text, BinaryFormat, TextEncoding};
+use crate::error::ZpError;
+use crate::select::Selector;
+use crate::transform::Transform;
+use chrono::{Duration, Local};
+use clap::{ArgGroup, Parser, Subcommand};
 use is_terminal::IsTerminal;
-use std::io::{self, Read};
+use regex::Regex;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(
     author = "Gokul <@bahdotsh>",
     version = env!("CARGO_PKG_VERSION"),
     about = "Tool to copy contents from a file",
+    args_conflicts_with_subcommands = true
 )]
+#[command(group(
+    ArgGroup::new("selector")
+        .args(["grep", "between", "json_path", "yaml_path"])
+        .conflicts_with_all(["start", "end"])
+))]
 pub struct Zp {
-    pub source: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[clap(help = "Files to copy; several files or globs are copied together, - reads stdin")]
+    pub sources: Vec<String>,
+    #[clap(
+        long,
+        conflicts_with_all = ["sources", "file", "text"],
+        help = "Copy standard input"
+    )]
+    pub stdin: bool,
+    #[clap(
+        long,
+        requires = "sources",
+        conflicts_with = "text",
+        help = "Treat the sources as files even when standard input is piped"
+    )]
+    pub file: bool,
+    #[clap(
+        long,
+        value_name = "TEXT",
+        conflicts_with = "sources",
+        help = "Copy this text instead of reading a file or standard input"
+    )]
+    pub text: Option<String>,
     #[clap(short, long)]
     pub start: Option<usize>,
     #[clap(short, long)]
     pub end: Option<usize>,
+    #[clap(
+        long,
+        value_enum,
+        help = "Decode the input from this encoding instead of detecting UTF-8/UTF-16"
+    )]
+    pub encoding: Option<TextEncoding>,
+    #[clap(
+        long,
+        value_enum,
+        conflicts_with = "encoding",
+        help = "Copy the raw bytes of the input encoded as base64 or hex"
+    )]
+    pub binary: Option<BinaryFormat>,
+
+    #[clap(
+        long,
+        value_name = "REGEX",
+        help = "Copy the lines matching a regular expression"
+    )]
+    pub grep: Option<String>,
+    #[clap(
+        short = 'C',
+        long,
+        requires = "grep",
+        value_name = "LINES",
+        help = "Lines of context to copy around each --grep match"
+    )]
+    pub context: Option<usize>,
+    #[clap(
+        long,
+        num_args = 2,
+        value_names = ["START", "END"],
+        help = "Copy the blocks from a line matching START through the next line matching END"
+    )]
+    pub between: Option<Vec<String>>,
+    #[clap(
+        long = "json-path",
+        value_name = "PATH",
+        help = "Copy a value from a JSON file, e.g. servers[0].name"
+    )]
+    pub json_path: Option<String>,
+    #[clap(
+        long = "yaml-path",
+        value_name = "PATH",
+        help = "Copy a value from a YAML file, e.g. services.web.image"
+    )]
+    pub yaml_path: Option<String>,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value_t,
+        help = "Header written before each file when copying several files"
+    )]
+    pub header: HeaderStyle,
+    #[clap(
+        long = "max-size",
+        value_name = "SIZE",
+        value_parser = parse_size,
+        help = "Largest combined size to copy from several files, e.g. 512K or 10M [default: 5M]"
+    )]
+    pub max_size: Option<u64>,
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        value_name = "TRANSFORMS",
+        help = "Transform the text before copying, e.g. dedent,fence; applied in order"
+    )]
+    pub transform: Vec<Transform>,
+    #[clap(
+        long,
+        value_name = "DURATION",
+        value_parser = parse_expire,
+        conflicts_with = "logs",
+        help = "Clear the clipboard and expire the history entry after DURATION, e.g. 30s"
+    )]
+    pub expire: Option<Duration>,
+    /// Runs the clipboard clearer started by `--expire`.
+    #[clap(long = "clear-after", hide = true, value_name = "SECONDS")]
+    pub clear_after: Option<u64>,
     #[clap(short, long)]
     pub logs: bool,
+    #[clap(
+        long,
+        requires = "logs",
+        help = "Write the entry chosen in --logs to stdout instead of the clipboard"
+    )]
+    pub print: bool,
+    #[clap(
+        long,
+        conflicts_with_all = ["sources", "file", "text"],
+        help = "Copy standard input while passing it through to standard output"
+    )]
+    pub tee: bool,
 
     #[clap(long, short, help = "Start the clipboard monitoring daemon")]
     pub daemon: bool,
@@ -71,33 +203,281 @@ pub struct Zp {
     pub test_peer: Option<String>,
 }
 
+#[derive(Subcommand)]
+pub enum Command {
+    /// Inspect and edit clipboard history from scripts
+    #[command(subcommand)]
+    History(HistoryCommand),
+    /// Print the clipboard or a history entry, or write it to a file
+    Paste {
+        #[clap(
+            long,
+            help = "History entry index or id to paste instead of the clipboard"
+        )]
+        entry: Option<String>,
+        #[clap(
+            long,
+            value_name = "FILE",
+            help = "Write to this file instead of stdout"
+        )]
+        to: Option<PathBuf>,
+        #[clap(
+            long,
+            requires = "to",
+            help = "Append to the file instead of replacing it"
+        )]
+        append: bool,
+    },
+}
+
+/// Entries are referred to by index (0 is the most recent) or by id, where a
+/// unique id prefix is enough.
+#[derive(Subcommand)]
+pub enum HistoryCommand {
+    /// List the most recent entries
+    List {
+        #[clap(short = 'n', long, help = "Maximum number of entries to list")]
+        limit: Option<usize>,
+        #[clap(long, help = "Print entries as JSON")]
+        json: bool,
+    },
+    /// Print the content of a single entry
+    Show {
+        #[clap(help = "Entry index or id")]
+        entry: String,
+        #[clap(long, help = "Print the entry with its metadata as JSON")]
+        json: bool,
+        #[clap(
+            short,
+            long,
+            conflicts_with = "json",
+            help = "Write the entry to a file instead of stdout; images are written as PNG"
+        )]
+        output: Option<PathBuf>,
+    },
+    /// List entries containing a pattern
+    Search {
+        pattern: String,
+        #[clap(short = 'i', long = "ignore-case", help = "Match case-insensitively")]
+        ignore_case: bool,
+        #[clap(short = 'n', long, help = "Maximum number of entries to list")]
+        limit: Option<usize>,
+        #[clap(long, help = "Print entries as JSON")]
+        json: bool,
+    },
+    /// Delete an entry
+    #[command(alias = "delete")]
+    Rm {
+        #[clap(help = "Entry index or id")]
+        entry: String,
+    },
+    /// Delete all entries, or only older ones
+    Clear {
+        #[clap(
+            long,
+            help = "Only delete entries older than this date (YYYY-MM-DD, RFC 3339 or a duration like 30d)"
+        )]
+        before: Option<String>,
+    },
+    /// Encrypt the history file with a key file or a passphrase
+    Encrypt {
+        #[clap(
+            long,
+            help = "Derive the key from a passphrase instead of using a key file"
+        )]
+        passphrase: bool,
+        #[clap(
+            long = "key-file",
+            value_name = "FILE",
+            conflicts_with = "passphrase",
+            help = "Key file to use, created if missing [default: ~/.zp/history.key]"
+        )]
+        key_file: Option<PathBuf>,
+    },
+    /// Decrypt the history file back into the configured backend
+    Decrypt,
+}
+
+/// Where the text to copy comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    Stdin,
+    Text,
+    Files,
+}
+
+impl InputMode {
+    /// Picks the input from `--text`, `--stdin`, `--tee`, `--file` or a `-` source.
+    ///
+    /// Without any of them, sources are read as files, and with no sources piped
+    /// standard input is copied. Whether stdout is a terminal doesn't matter, so the
+    /// same command behaves alike under cron, CI, editors and `ssh -t`.
+    pub fn detect(zp: &Zp, stdin_is_terminal: bool) -> Result<InputMode, ZpError> {
+        if zp.text.is_some() {
+            return Ok(InputMode::Text);
+        }
+        if zp.stdin || zp.tee {
+            return Ok(InputMode::Stdin);
+        }
+        if zp.file {
+            return Ok(InputMode::Files);
+        }
+
+        match zp.sources.as_slice() {
+            [source] if source == "-" => Ok(InputMode::Stdin),
+            [] if !stdin_is_terminal => Ok(InputMode::Stdin),
+            [] => Err(ZpError::Parse("No source to copy from".to_string())),
+            sources if sources.iter().any(|source| source == "-") => Err(ZpError::Parse(
+                "- can't be combined with other sources".to_string(),
+            )),
+            _ => Ok(InputMode::Files),
+        }
+    }
+}
+
 pub struct Query {
-    pub source: String,
+    /// Text read from standard input or given with `--text`; `None` when copying files.
+    pub text: Option<String>,
     pub start: usize,
     pub end: usize,
+    pub selector: Option<Selector>,
 }
 
 impl Query {
-    pub fn build(zp: &Zp) -> Result<Query, &'static str> {
-        let mut source = String::new();
-
-        if io::stdout().is_terminal() && io::stderr().is_terminal() && !io::stdin().is_terminal() {
-            let mut buffer = io::stdin();
-            while let Ok(n) = buffer.read_to_string(&mut source) {
-                if n == 0 {
-                    break;
-                }
+    pub fn build(zp: &Zp) -> Result<Query, ZpError> {
+        let text = match InputMode::detect(zp, io::stdin().is_terminal())? {
+            InputMode::Stdin => {
+                let input = read_stdin(zp.tee)?;
+                Some(bytes_to_text(&input, zp.encoding, zp.binary)?)
             }
-        } else {
-            source = match &zp.source {
-                Some(arg) => arg.to_owned(),
-                None => return Err("No source to copy from"),
-            };
-        }
+            InputMode::Text => zp.text.clone(),
+            InputMode::Files => None,
+        };
 
         let start = zp.start.unwrap_or(0);
         let end = zp.end.unwrap_or(0);
+        let selector = Self::build_selector(zp)?;
+
+        Ok(Query {
+            text,
+            start,
+            end,
+            selector,
+        })
+    }
+
+    fn build_selector(zp: &Zp) -> Result<Option<Selector>, ZpError> {
+        let regex = |pattern: &str| {
+            Regex::new(pattern)
+                .map_err(|e| ZpError::Parse(format!("Invalid pattern '{}': {}", pattern, e)))
+        };
+
+        let selector = if let Some(pattern) = &zp.grep {
+            Selector::Grep {
+                pattern: regex(pattern)?,
+                context: zp.context.unwrap_or(0),
+            }
+        } else if let Some([start, end]) = zp.between.as_deref() {
+            Selector::Between {
+                start: regex(start)?,
+                end: regex(end)?,
+            }
+        } else if let Some(path) = &zp.json_path {
+            Selector::JsonPath(path.clone())
+        } else if let Some(path) = &zp.yaml_path {
+            Selector::YamlPath(path.clone())
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(selector))
+    }
+}
+
+/// Reads all of standard input, copying it to standard output as it arrives when
+/// `tee` is set.
+fn read_stdin(tee: bool) -> io::Result<Vec<u8>> {
+    let mut input = vec![];
+    if !tee {
+        io::stdin().read_to_end(&mut input)?;
+        return Ok(input);
+    }
+
+    let mut stdin = io::stdin().lock();
+    let mut stdout = io::stdout().lock();
+    let mut passthrough = true;
+    let mut buffer = [0; 8192];
+    loop {
+        let n = match stdin.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        input.extend_from_slice(&buffer[..n]);
+
+        // Keep capturing when the reader on the other end goes away
+        if passthrough {
+            match stdout.write_all(&buffer[..n]).and_then(|_| stdout.flush()) {
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => passthrough = false,
+                result => result?,
+            }
+        }
+    }
+    Ok(input)
+}
+
+/// Parses the `--expire` duration, which must be at least a second and end at a
+/// time that can be represented.
+fn parse_expire(input: &str) -> Result<Duration, ZpError> {
+    let ttl = parse_duration(input)?;
+    if ttl.num_seconds() < 1 {
+        return Err(ZpError::Parse(
+            "--expire needs a duration of at least 1s".to_string(),
+        ));
+    }
+    if Local::now().checked_add_signed(ttl).is_none() {
+        return Err(ZpError::Parse(format!("Duration '{}' is too long", input)));
+    }
+    Ok(ttl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(args: &[&str], stdin_is_terminal: bool) -> Option<InputMode> {
+        let zp = Zp::try_parse_from(std::iter::once("zp").chain(args.iter().copied())).unwrap();
+        InputMode::detect(&zp, stdin_is_terminal).ok()
+    }
+
+    #[test]
+    fn test_explicit_input_modes() {
+        assert_eq!(detect(&["--stdin"], true), Some(InputMode::Stdin));
+        assert_eq!(detect(&["--tee"], true), Some(InputMode::Stdin));
+        assert_eq!(detect(&["-"], true), Some(InputMode::Stdin));
+        assert_eq!(detect(&["--text", "hello"], false), Some(InputMode::Text));
+        assert_eq!(detect(&["--file", "-"], false), Some(InputMode::Files));
+        assert!(detect(&["a.txt", "-"], false).is_none());
+
+        assert!(Zp::try_parse_from(["zp", "--stdin", "a.txt"]).is_err());
+        assert!(Zp::try_parse_from(["zp", "--text", "hello", "a.txt"]).is_err());
+        assert!(Zp::try_parse_from(["zp", "--file"]).is_err());
+    }
+
+    #[test]
+    fn test_detect_fallback() {
+        // Sources are files even when stdin is piped, e.g. under cron or CI
+        assert_eq!(detect(&["a.txt"], false), Some(InputMode::Files));
+        assert_eq!(detect(&["a.txt"], true), Some(InputMode::Files));
+        // Without sources, piped stdin is copied
+        assert_eq!(detect(&[], false), Some(InputMode::Stdin));
+        assert!(detect(&[], true).is_none());
+    }
 
-        Ok(Query { source, start, end })
+    #[test]
+    fn test_parse_expire() {
+        assert_eq!(parse_expire("30s").unwrap(), Duration::seconds(30));
+        assert!(parse_expire("0s").is_err());
+        assert!(parse_expire("99999999999d").is_err());
     }
 }
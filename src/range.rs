@@ -0,0 +1,309 @@
+use std::fmt;
+
+/// A part of a file selected with a range spec such as `10-42` or `10:5-12:20`.
+///
+/// Lines and columns are 1-based and inclusive; columns count characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineRange {
+    /// From `start` up to `end`, or to the end of the file when `end` is `None`.
+    Span {
+        start: Position,
+        end: Option<Position>,
+    },
+    /// The last `n` lines.
+    Last(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    /// `None` selects from the start of the line, or up to its end.
+    pub column: Option<usize>,
+}
+
+/// Text picked out of a file, with the 1-based inclusive line ranges it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub text: String,
+    pub lines: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct RangeError(String);
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+/// Splits `source` into a file path and the ranges that follow it, as in `file.rs:10-42`.
+///
+/// Returns `None` if `source` names an existing file itself or no prefix of it does.
+/// `is_file` is used to find where the path ends, so paths containing `:` still work.
+pub fn split_range_spec(
+    source: &str,
+    is_file: impl Fn(&str) -> bool,
+) -> Option<(&str, Result<Vec<LineRange>, RangeError>)> {
+    if is_file(source) {
+        return None;
+    }
+
+    let mut candidates = source
+        .match_indices(':')
+        .map(|(i, _)| (&source[..i], &source[i + 1..]))
+        .filter(|(path, _)| !path.is_empty() && is_file(path))
+        .map(|(path, spec)| (path, parse_ranges(spec)));
+    let first = candidates.next()?;
+    if first.1.is_ok() {
+        return Some(first);
+    }
+    Some(
+        candidates
+            .find(|(_, ranges)| ranges.is_ok())
+            .unwrap_or(first),
+    )
+}
+
+/// Parses comma separated ranges: `N`, `N-M`, `N-`, `-N` (the last N lines) and
+/// line/column spans such as `10:5-12:20`.
+pub fn parse_ranges(spec: &str) -> Result<Vec<LineRange>, RangeError> {
+    spec.split(',')
+        .map(|item| parse_range(item.trim()))
+        .collect()
+}
+
+fn parse_range(item: &str) -> Result<LineRange, RangeError> {
+    let invalid = || RangeError(format!("invalid range '{}'", item));
+
+    if let Some(count) = item.strip_prefix('-') {
+        return match count.parse() {
+            Ok(count) if count > 0 => Ok(LineRange::Last(count)),
+            _ => Err(invalid()),
+        };
+    }
+
+    let (start, end) = match item.split_once('-') {
+        Some((start, "")) => (parse_position(start).ok_or_else(invalid)?, None),
+        Some((start, end)) => (
+            parse_position(start).ok_or_else(invalid)?,
+            Some(parse_position(end).ok_or_else(invalid)?),
+        ),
+        None => {
+            let position = parse_position(item).ok_or_else(invalid)?;
+            (position, Some(position))
+        }
+    };
+
+    if let Some(end) = end {
+        if (end.line, end.column.unwrap_or(usize::MAX)) < (start.line, start.column.unwrap_or(1)) {
+            return Err(RangeError(format!(
+                "range '{}' ends before it starts",
+                item
+            )));
+        }
+    }
+    Ok(LineRange::Span { start, end })
+}
+
+fn parse_position(text: &str) -> Option<Position> {
+    let (line, column) = match text.split_once(':') {
+        Some((line, column)) => (line, Some(column.parse().ok().filter(|&c| c > 0)?)),
+        None => (text, None),
+    };
+    let line = line.parse().ok().filter(|&l| l > 0)?;
+    Some(Position { line, column })
+}
+
+/// Picks `ranges` out of `contents`, joining the pieces with newlines.
+pub fn select_ranges(contents: &str, ranges: &[LineRange]) -> Result<Selection, RangeError> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut pieces = vec![];
+    let mut line_ranges = vec![];
+
+    for range in ranges {
+        let (start, end) = match *range {
+            LineRange::Last(count) => (
+                Position {
+                    line: lines.len().saturating_sub(count) + 1,
+                    column: None,
+                },
+                None,
+            ),
+            LineRange::Span { start, end } => (start, end),
+        };
+        if start.line > lines.len() {
+            return Err(RangeError(format!(
+                "line {} is past the end of the file ({} lines)",
+                start.line,
+                lines.len()
+            )));
+        }
+
+        let end_line = end.map_or(lines.len(), |end| end.line.min(lines.len()));
+        let mut selected: Vec<String> = lines[start.line - 1..end_line]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+
+        // Trim the end first so both columns of a single-line span refer to the
+        // original line
+        if let Some(column) = end
+            .filter(|end| end.line <= lines.len())
+            .and_then(|end| end.column)
+        {
+            let last = selected.last_mut().unwrap();
+            *last = last.chars().take(column).collect();
+        }
+        if let Some(column) = start.column {
+            selected[0] = selected[0].chars().skip(column - 1).collect();
+        }
+
+        pieces.push(selected.join("\n"));
+        line_ranges.push((start.line, end_line));
+    }
+
+    Ok(Selection {
+        text: pieces.join("\n"),
+        lines: line_ranges,
+    })
+}
+
+/// Applies the original `--start/--end` options.
+///
+/// With neither set the whole of `contents` is selected. With only `start` it is the
+/// number of leading words to select. With `end`, `start` and `end` are 0-based
+/// inclusive line numbers.
+pub fn select_legacy(contents: &str, start: usize, end: usize) -> Result<Selection, RangeError> {
+    if end == 0 {
+        let text = if start == 0 {
+            contents.to_string()
+        } else {
+            let words: Vec<&str> = contents.split_whitespace().take(start).collect();
+            words.join(" ")
+        };
+        return Ok(Selection {
+            text,
+            lines: vec![],
+        });
+    }
+
+    let line_count = contents.lines().count();
+    if start >= line_count {
+        return Err(RangeError(format!(
+            "line {} is past the end of the file ({} lines)",
+            start + 1,
+            line_count
+        )));
+    }
+    if start > end {
+        return Err(RangeError(format!(
+            "--start {} is after --end {}",
+            start, end
+        )));
+    }
+
+    let lines: Vec<&str> = contents
+        .lines()
+        .enumerate()
+        .filter(|&(i, _)| i >= start && i <= end)
+        .map(|(_, line)| line)
+        .collect();
+    let last = end.min(line_count - 1);
+    Ok(Selection {
+        text: lines.join("\n"),
+        lines: vec![(start + 1, last + 1)],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENTS: &str = "one two\nthree four\nfive six\nseven eight\n";
+
+    fn select(spec: &str) -> Result<Selection, RangeError> {
+        select_ranges(CONTENTS, &parse_ranges(spec)?)
+    }
+
+    #[test]
+    fn test_legacy_start_end() {
+        // Neither option copies everything
+        assert_eq!(select_legacy(CONTENTS, 0, 0).unwrap().text, CONTENTS);
+        // --start alone takes the first N words
+        let words = select_legacy(CONTENTS, 3, 0).unwrap();
+        assert_eq!(words.text, "one two three");
+        assert!(words.lines.is_empty());
+        // --end selects 0-based inclusive lines, recorded 1-based
+        let lines = select_legacy(CONTENTS, 1, 2).unwrap();
+        assert_eq!(lines.text, "three four\nfive six");
+        assert_eq!(lines.lines, vec![(2, 3)]);
+        // An end past the last line is clamped, but a start past it is an error
+        assert_eq!(select_legacy(CONTENTS, 3, 10).unwrap().lines, vec![(4, 4)]);
+        assert!(select_legacy(CONTENTS, 10, 20).is_err());
+        assert!(select_legacy(CONTENTS, 3, 1).is_err());
+    }
+
+    #[test]
+    fn test_line_ranges() {
+        assert_eq!(select("2-3").unwrap().text, "three four\nfive six");
+        assert_eq!(select("2").unwrap().text, "three four");
+        assert_eq!(select("3-").unwrap().text, "five six\nseven eight");
+        assert_eq!(select("-2").unwrap().lines, vec![(3, 4)]);
+        assert_eq!(select("-10").unwrap().text, CONTENTS.trim_end());
+
+        let multiple = select("1,3-4").unwrap();
+        assert_eq!(multiple.text, "one two\nfive six\nseven eight");
+        assert_eq!(multiple.lines, vec![(1, 1), (3, 4)]);
+    }
+
+    #[test]
+    fn test_column_spans() {
+        assert_eq!(select("1:5-2:5").unwrap().text, "two\nthree");
+        assert_eq!(select("2:7-2:10").unwrap().text, "four");
+        assert_eq!(select("3:6-3").unwrap().text, "six");
+    }
+
+    #[test]
+    fn test_invalid_ranges() {
+        assert!(parse_ranges("0").is_err());
+        assert!(parse_ranges("5-2").is_err());
+        assert!(parse_ranges("a-b").is_err());
+        assert!(parse_ranges("1:0-2").is_err());
+        assert!(select("9").is_err());
+    }
+
+    #[test]
+    fn test_split_range_spec() {
+        let is_file = |path: &str| path == "src/main.rs" || path == "a:b.txt";
+        assert_eq!(split_range_spec("src/main.rs", is_file), None);
+        assert_eq!(split_range_spec("a:b.txt", is_file), None);
+        assert_eq!(split_range_spec("missing.rs:1-2", is_file), None);
+
+        let (path, ranges) = split_range_spec("src/main.rs:10:5-12:20", is_file).unwrap();
+        assert_eq!(path, "src/main.rs");
+        assert_eq!(
+            ranges,
+            Ok(vec![LineRange::Span {
+                start: Position {
+                    line: 10,
+                    column: Some(5)
+                },
+                end: Some(Position {
+                    line: 12,
+                    column: Some(20)
+                }),
+            }])
+        );
+
+        let (path, ranges) = split_range_spec("a:b.txt:-20", is_file).unwrap();
+        assert_eq!(path, "a:b.txt");
+        assert_eq!(ranges, Ok(vec![LineRange::Last(20)]));
+
+        let (path, ranges) = split_range_spec("src/main.rs:abc", is_file).unwrap();
+        assert_eq!(path, "src/main.rs");
+        assert!(ranges.is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use crate::config::RetentionPolicy;
+use crate::duration::parse_duration;
+use crate::history::{parse_entry_time, ClipboardHistoryEntry};
+use chrono::{DateTime, Local};
+
+impl RetentionPolicy {
+    /// Returns true when the policy can remove entries from an existing history.
+    pub fn prunes(&self) -> bool {
+        self.max_entries.is_some()
+            || self.max_total_bytes.is_some()
+            || self.max_age.is_some()
+            || self.max_entry_bytes.is_some()
+    }
+}
+
+/// Bytes `entry` takes up, which for images is the size of the PNG blob. Entries
+/// recorded before sizes were tracked fall back to the content length.
+fn entry_bytes(entry: &ClipboardHistoryEntry) -> usize {
+    if entry.size > 0 {
+        entry.size
+    } else {
+        entry.content.len()
+    }
+}
+
+/// Whether `entry` is within the per-entry limits (age and size) of `policy`, and
+/// hasn't expired.
+///
+/// Used to reject new and incoming entries before they are stored.
+pub fn is_retained(
+    entry: &ClipboardHistoryEntry,
+    policy: &RetentionPolicy,
+    now: DateTime<Local>,
+) -> bool {
+    // An expiry asked for explicitly applies even to pinned entries
+    if entry.is_expired(now) {
+        return false;
+    }
+    if entry.pinned {
+        return true;
+    }
+
+    let too_large = policy
+        .max_entry_bytes
+        .is_some_and(|max| entry_bytes(entry) > max);
+    let too_old = policy
+        .max_age
+        .as_deref()
+        .and_then(|max_age| parse_duration(max_age).ok())
+        .is_some_and(|max_age| now - parse_entry_time(&entry.timestamp) > max_age);
+
+    !too_large && !too_old
+}
+
+/// Removes the entries of `entries` (oldest first) that fall outside `policy`,
+/// dropping the oldest first once count or size limits are exceeded. Pinned entries
+/// are never removed and don't count towards `max_entries`.
+///
+/// Returns the number of entries removed.
+pub fn apply_retention(
+    entries: &mut Vec<ClipboardHistoryEntry>,
+    policy: &RetentionPolicy,
+    now: DateTime<Local>,
+) -> usize {
+    let original_len = entries.len();
+    entries.retain(|entry| is_retained(entry, policy, now));
+
+    if let Some(max_entries) = policy.max_entries {
+        let mut excess = entries
+            .iter()
+            .filter(|entry| !entry.pinned)
+            .count()
+            .saturating_sub(max_entries);
+        entries.retain(|entry| {
+            if excess > 0 && !entry.pinned {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    if let Some(max_total_bytes) = policy.max_total_bytes {
+        let mut total: usize = entries.iter().map(entry_bytes).sum();
+        entries.retain(|entry| {
+            if total > max_total_bytes && !entry.pinned {
+                total -= entry_bytes(entry);
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    original_len - entries.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::EntrySource;
+    use chrono::Duration;
+
+    fn history(contents: &[&str]) -> Vec<ClipboardHistoryEntry> {
+        contents
+            .iter()
+            .map(|c| ClipboardHistoryEntry::new(c.to_string(), EntrySource::Manual))
+            .collect()
+    }
+
+    fn contents(entries: &[ClipboardHistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.content.as_str()).collect()
+    }
+
+    #[test]
+    fn test_max_entries_keeps_newest_and_pinned() {
+        let mut entries = history(&["a", "b", "c", "d"]);
+        entries[0].pinned = true;
+        let policy = RetentionPolicy {
+            max_entries: Some(2),
+            ..Default::default()
+        };
+
+        assert_eq!(apply_retention(&mut entries, &policy, Local::now()), 1);
+        assert_eq!(contents(&entries), vec!["a", "c", "d"]);
+    }
+
+    #[test]
+    fn test_max_total_bytes_and_entry_size() {
+        let mut entries = history(&["aaaa", "bbbb", "cccccccccc", "dd"]);
+        let policy = RetentionPolicy {
+            max_total_bytes: Some(6),
+            max_entry_bytes: Some(8),
+            ..Default::default()
+        };
+
+        apply_retention(&mut entries, &policy, Local::now());
+        assert_eq!(contents(&entries), vec!["bbbb", "dd"]);
+    }
+
+    #[test]
+    fn test_image_size_counts_the_blob() {
+        let mut entries = history(&["[image 800x600]", "text"]);
+        entries[0].size = 2_000_000;
+        let policy = RetentionPolicy {
+            max_entry_bytes: Some(1_000_000),
+            ..Default::default()
+        };
+
+        assert!(!is_retained(&entries[0], &policy, Local::now()));
+        apply_retention(&mut entries, &policy, Local::now());
+        assert_eq!(contents(&entries), vec!["text"]);
+
+        let mut entries = history(&["[image 800x600]", "text"]);
+        entries[0].size = 2_000_000;
+        let policy = RetentionPolicy {
+            max_total_bytes: Some(1_000_000),
+            ..Default::default()
+        };
+        assert_eq!(apply_retention(&mut entries, &policy, Local::now()), 1);
+        assert_eq!(contents(&entries), vec!["text"]);
+    }
+
+    #[test]
+    fn test_max_age() {
+        let mut entries = history(&["old", "new"]);
+        entries[0].timestamp = (Local::now() - Duration::days(10)).to_rfc3339();
+        let policy = RetentionPolicy {
+            max_age: Some("7d".to_string()),
+            ..Default::default()
+        };
+
+        assert!(!is_retained(&entries[0], &policy, Local::now()));
+        apply_retention(&mut entries, &policy, Local::now());
+        assert_eq!(contents(&entries), vec!["new"]);
+    }
+
+    #[test]
+    fn test_expired_entries() {
+        let mut entries = history(&["secret", "pinned secret", "kept"]);
+        for entry in &mut entries[..2] {
+            *entry = entry.clone().expiring_in(Duration::seconds(30)).unwrap();
+        }
+        entries[1].pinned = true;
+        let policy = RetentionPolicy::default();
+
+        assert_eq!(apply_retention(&mut entries, &policy, Local::now()), 0);
+        let later = Local::now() + Duration::seconds(31);
+        assert_eq!(apply_retention(&mut entries, &policy, later), 2);
+        assert_eq!(contents(&entries), vec!["kept"]);
+    }
+}
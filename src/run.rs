@@ -1,17 +1,138 @@
-use crate::clipboard::cpy;
-use crate::file::read_file_content;
+use crate::clipboard::{cpy, cpy_image, source_refs};
+use crate::concat::{format_file, join_files, DEFAULT_MAX_SIZE};
+use crate::error::ZpError;
+use crate::file::{expand_globs, read_file, FileContent};
 use crate::query::{Query, Zp};
-use is_terminal::IsTerminal;
-use std::io;
-
-pub fn run(zp: Zp) -> Result<(), std::io::Error> {
-    let query = Query::build(&zp).unwrap();
-    if io::stdout().is_terminal() && io::stderr().is_terminal() && !io::stdin().is_terminal() {
-        cpy(&query.source, query.start, query.end);
-    } else {
-        let contents = read_file_content(&query.source)?;
-        cpy(&contents, query.start, query.end);
+use crate::range::{select_legacy, select_ranges, split_range_spec, Selection};
+use crate::transform::apply_all;
+use arboard::ImageData;
+use std::path::Path;
+
+/// What was read and selected from one source file.
+enum Copied<'a> {
+    Text {
+        path: &'a str,
+        selection: Selection,
+    },
+    Image {
+        path: &'a str,
+        image: ImageData<'static>,
+    },
+}
+
+pub fn run(zp: Zp) -> Result<(), ZpError> {
+    let query = Query::build(&zp)?;
+    if let Some(text) = &query.text {
+        let selection = match &query.selector {
+            Some(selector) => selector.select(text)?,
+            None => select_legacy(text, query.start, query.end)?,
+        };
+        return cpy(
+            apply_all(selection.text, &zp.transform, None),
+            vec![],
+            zp.expire,
+        );
+    }
+
+    let sources = expand_globs(&zp.sources)?;
+    if let [source] = sources.as_slice() {
+        return match read_source(&zp, &query, source)? {
+            Copied::Text { path, selection } => {
+                let sources = source_refs(path, &selection.lines);
+                cpy(
+                    apply_all(selection.text, &zp.transform, Some(path)),
+                    sources,
+                    zp.expire,
+                )
+            }
+            Copied::Image { path, .. } if !zp.transform.is_empty() => Err(ZpError::Parse(format!(
+                "{} is an image, which can't be transformed",
+                path
+            ))),
+            Copied::Image { path, .. } if zp.expire.is_some() => Err(ZpError::Parse(format!(
+                "{} is an image; --expire only applies to text",
+                path
+            ))),
+            Copied::Image { path, image } => cpy_image(image, path),
+        };
+    }
+
+    if zp.start.is_some() || zp.end.is_some() {
+        return Err(ZpError::Parse(
+            "--start/--end only apply to a single file; use file:range specs instead".to_string(),
+        ));
+    }
+
+    let max_size = zp.max_size.unwrap_or(DEFAULT_MAX_SIZE);
+    let mut parts = vec![];
+    let mut source_paths = vec![];
+    let mut total: u64 = 0;
+    for source in &sources {
+        match read_source(&zp, &query, source)? {
+            Copied::Text { path, selection } => {
+                let part = format_file(path, &selection.text, zp.header);
+                // Stop reading once over the limit, e.g. for `**/*` in a large tree
+                total += part.len() as u64;
+                if total > max_size {
+                    return Err(too_large(sources.len(), max_size));
+                }
+                parts.push(part);
+                source_paths.extend(source_refs(path, &selection.lines));
+            }
+            Copied::Image { path, .. } => {
+                return Err(ZpError::Parse(format!(
+                    "{} is an image, which can't be copied together with other files",
+                    path
+                )));
+            }
+        }
+    }
+
+    // Transforms can make the text longer
+    let text = apply_all(join_files(&parts, zp.header), &zp.transform, None);
+    if text.len() as u64 > max_size {
+        return Err(too_large(sources.len(), max_size));
     }
+    cpy(text, source_paths, zp.expire)
+}
 
-    Ok(())
+fn too_large(files: usize, max_size: u64) -> ZpError {
+    ZpError::Parse(format!(
+        "the {} files add up to more than the limit of {} bytes; raise it with --max-size",
+        files, max_size
+    ))
+}
+
+/// Reads `source`, which may end with a range spec such as `file.rs:10-42`, and
+/// applies the selection options to it.
+fn read_source<'a>(zp: &Zp, query: &Query, source: &'a str) -> Result<Copied<'a>, ZpError> {
+    // `file.rs:10-42` selects ranges instead of using --start/--end
+    let (path, ranges) = match split_range_spec(source, |p| Path::new(p).is_file()) {
+        Some(_) if zp.start.is_some() || zp.end.is_some() || query.selector.is_some() => {
+            return Err(ZpError::Parse(
+                "a range spec can't be combined with --start/--end or a selector".to_string(),
+            ));
+        }
+        Some((path, ranges)) => (path, Some(ranges?)),
+        None => (source, None),
+    };
+
+    match read_file(path, zp.encoding, zp.binary)? {
+        FileContent::Text(contents) => {
+            let selection = match (&ranges, &query.selector) {
+                (Some(ranges), _) => select_ranges(&contents, ranges)
+                    .map_err(|e| ZpError::Parse(format!("{}: {}", path, e)))?,
+                (None, Some(selector)) => selector
+                    .select(&contents)
+                    .map_err(|e| ZpError::Parse(format!("{}: {}", path, e)))?,
+                (None, None) => select_legacy(&contents, query.start, query.end)
+                    .map_err(|e| ZpError::Parse(format!("{}: {}", path, e)))?,
+            };
+            Ok(Copied::Text { path, selection })
+        }
+        FileContent::Image(_) if ranges.is_some() || query.selector.is_some() => Err(
+            ZpError::Parse("parts can't be selected from images".to_string()),
+        ),
+        FileContent::Image(image) => Ok(Copied::Image { path, image }),
+    }
 }
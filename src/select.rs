@@ -0,0 +1,306 @@
+use crate::range::Selection;
+use regex::Regex;
+use std::fmt;
+
+/// Picks part of the input by content rather than by position.
+#[derive(Debug, Clone)]
+pub enum Selector {
+    /// Lines matching the pattern, with `context` lines around each match.
+    Grep { pattern: Regex, context: usize },
+    /// Blocks from a line matching `start` through the next line matching `end`.
+    Between { start: Regex, end: Regex },
+    /// A value from a JSON document.
+    JsonPath(String),
+    /// A value from a YAML document.
+    YamlPath(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SelectError(String);
+
+impl fmt::Display for SelectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SelectError {}
+
+impl Selector {
+    /// Applies the selector to `contents`.
+    pub fn select(&self, contents: &str) -> Result<Selection, SelectError> {
+        match self {
+            Selector::Grep { pattern, context } => grep(contents, pattern, *context),
+            Selector::Between { start, end } => between(contents, start, end),
+            Selector::JsonPath(path) => {
+                let value: serde_json::Value = serde_json::from_str(contents)
+                    .map_err(|e| SelectError(format!("input is not valid JSON: {}", e)))?;
+                let value = lookup_json(&value, &parse_path(path)?)
+                    .ok_or_else(|| SelectError(format!("no value at '{}'", path)))?;
+                let text = match value {
+                    serde_json::Value::String(text) => text.clone(),
+                    value => serde_json::to_string_pretty(value).unwrap(),
+                };
+                Ok(Selection {
+                    text,
+                    lines: vec![],
+                })
+            }
+            Selector::YamlPath(path) => {
+                let value: serde_yaml::Value = serde_yaml::from_str(contents)
+                    .map_err(|e| SelectError(format!("input is not valid YAML: {}", e)))?;
+                let value = lookup_yaml(&value, &parse_path(path)?)
+                    .ok_or_else(|| SelectError(format!("no value at '{}'", path)))?;
+                let text = match value {
+                    serde_yaml::Value::String(text) => text.clone(),
+                    value => serde_yaml::to_string(value)
+                        .map_err(|e| SelectError(e.to_string()))?
+                        .trim_end()
+                        .to_string(),
+                };
+                Ok(Selection {
+                    text,
+                    lines: vec![],
+                })
+            }
+        }
+    }
+}
+
+fn grep(contents: &str, pattern: &Regex, context: usize) -> Result<Selection, SelectError> {
+    let lines: Vec<&str> = contents.lines().collect();
+
+    // Merge the context windows of nearby matches into 0-based inclusive groups
+    let mut groups: Vec<(usize, usize)> = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        if !pattern.is_match(line) {
+            continue;
+        }
+        let start = i.saturating_sub(context);
+        let end = (i + context).min(lines.len() - 1);
+        match groups.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = end,
+            _ => groups.push((start, end)),
+        }
+    }
+
+    if groups.is_empty() {
+        return Err(SelectError(format!("no lines match '{}'", pattern)));
+    }
+    Ok(join_groups(&lines, groups))
+}
+
+fn between(contents: &str, start: &Regex, end: &Regex) -> Result<Selection, SelectError> {
+    let lines: Vec<&str> = contents.lines().collect();
+
+    let mut groups = vec![];
+    let mut i = 0;
+    while let Some(offset) = lines[i..].iter().position(|line| start.is_match(line)) {
+        let block_start = i + offset;
+        let block_end = lines[block_start + 1..]
+            .iter()
+            .position(|line| end.is_match(line))
+            .map(|offset| block_start + 1 + offset)
+            .ok_or_else(|| {
+                SelectError(format!(
+                    "no line matches '{}' after line {}",
+                    end,
+                    block_start + 1
+                ))
+            })?;
+        groups.push((block_start, block_end));
+        i = block_end + 1;
+    }
+
+    if groups.is_empty() {
+        return Err(SelectError(format!("no lines match '{}'", start)));
+    }
+    Ok(join_groups(&lines, groups))
+}
+
+/// Joins 0-based inclusive line groups with newlines, recording them 1-based.
+fn join_groups(lines: &[&str], groups: Vec<(usize, usize)>) -> Selection {
+    let text = groups
+        .iter()
+        .map(|&(start, end)| lines[start..=end].join("\n"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Selection {
+        text,
+        lines: groups
+            .into_iter()
+            .map(|(start, end)| (start + 1, end + 1))
+            .collect(),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parses a path such as `.servers[0].name`, `servers.0.name` or `a["key.with.dots"]`.
+/// A leading `$` is allowed.
+fn parse_path(path: &str) -> Result<Vec<PathSegment>, SelectError> {
+    let invalid = || SelectError(format!("invalid path '{}'", path));
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+
+    let mut segments = vec![];
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let quote = after.chars().next().filter(|&c| c == '"' || c == '\'');
+            let (segment, after) = match quote {
+                Some(quote) => {
+                    let (key, after) = after[1..]
+                        .split_once(&format!("{}]", quote))
+                        .ok_or_else(invalid)?;
+                    (PathSegment::Key(key.to_string()), after)
+                }
+                None => {
+                    let (index, after) = after.split_once(']').ok_or_else(invalid)?;
+                    (
+                        PathSegment::Index(index.parse().map_err(|_| invalid())?),
+                        after,
+                    )
+                }
+            };
+            segments.push(segment);
+            rest = after;
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            let key = &rest[..end];
+            segments.push(match key.parse() {
+                Ok(index) => PathSegment::Index(index),
+                Err(_) => PathSegment::Key(key.to_string()),
+            });
+            rest = &rest[end..];
+        }
+    }
+    Ok(segments)
+}
+
+fn lookup_json<'a>(
+    value: &'a serde_json::Value,
+    path: &[PathSegment],
+) -> Option<&'a serde_json::Value> {
+    path.iter().try_fold(value, |value, segment| match segment {
+        PathSegment::Key(key) => value.get(key.as_str()),
+        PathSegment::Index(index) => match value {
+            serde_json::Value::Object(map) => map.get(&index.to_string()),
+            value => value.get(index),
+        },
+    })
+}
+
+fn lookup_yaml<'a>(
+    value: &'a serde_yaml::Value,
+    path: &[PathSegment],
+) -> Option<&'a serde_yaml::Value> {
+    path.iter().try_fold(value, |value, segment| match segment {
+        PathSegment::Key(key) => value.get(key.as_str()),
+        PathSegment::Index(index) => match value {
+            // Mapping keys may be numbers or numeric strings
+            serde_yaml::Value::Mapping(map) => map
+                .get(serde_yaml::Value::from(*index as u64))
+                .or_else(|| map.get(index.to_string().as_str())),
+            value => value.get(index),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str =
+        "use std::io;\n\nfn main() {\n    run();\n}\n\nfn run() {\n    todo!();\n}\n";
+
+    fn grep_lines(pattern: &str, context: usize) -> Selection {
+        Selector::Grep {
+            pattern: Regex::new(pattern).unwrap(),
+            context,
+        }
+        .select(SOURCE)
+        .unwrap()
+    }
+
+    #[test]
+    fn test_grep_with_context() {
+        let selection = grep_lines("^fn", 0);
+        assert_eq!(selection.text, "fn main() {\nfn run() {");
+        assert_eq!(selection.lines, vec![(3, 3), (7, 7)]);
+
+        // Overlapping context windows are merged
+        let selection = grep_lines("run", 1);
+        assert_eq!(selection.lines, vec![(3, 8)]);
+
+        let no_match = Selector::Grep {
+            pattern: Regex::new("missing").unwrap(),
+            context: 0,
+        };
+        assert!(no_match.select(SOURCE).is_err());
+    }
+
+    #[test]
+    fn test_between() {
+        let selector = Selector::Between {
+            start: Regex::new("^fn run").unwrap(),
+            end: Regex::new("^}").unwrap(),
+        };
+        let selection = selector.select(SOURCE).unwrap();
+        assert_eq!(selection.text, "fn run() {\n    todo!();\n}");
+        assert_eq!(selection.lines, vec![(7, 9)]);
+
+        let unterminated = Selector::Between {
+            start: Regex::new("^fn").unwrap(),
+            end: Regex::new("^end").unwrap(),
+        };
+        assert!(unterminated.select(SOURCE).is_err());
+    }
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            parse_path("$.servers[0].name").unwrap(),
+            vec![
+                PathSegment::Key("servers".to_string()),
+                PathSegment::Index(0),
+                PathSegment::Key("name".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_path("servers.1[\"a.b\"]").unwrap(),
+            vec![
+                PathSegment::Key("servers".to_string()),
+                PathSegment::Index(1),
+                PathSegment::Key("a.b".to_string()),
+            ]
+        );
+        assert!(parse_path("servers[x]").is_err());
+    }
+
+    #[test]
+    fn test_structured_paths() {
+        let json = r#"{"servers": [{"name": "web", "ports": [80, 443]}]}"#;
+        let select = |selector: Selector| selector.select(json).map(|s| s.text);
+        assert_eq!(
+            select(Selector::JsonPath("servers[0].name".to_string())),
+            Ok("web".to_string())
+        );
+        assert_eq!(
+            select(Selector::JsonPath("servers[0].ports[1]".to_string())),
+            Ok("443".to_string())
+        );
+        assert!(select(Selector::JsonPath("servers[1]".to_string())).is_err());
+
+        let yaml = "servers:\n  - name: web\n    ports: [80, 443]\n";
+        let selector = Selector::YamlPath(".servers[0]".to_string());
+        assert_eq!(
+            selector.select(yaml).unwrap().text,
+            "name: web\nports:\n- 80\n- 443"
+        );
+    }
+}
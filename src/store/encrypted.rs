@@ -0,0 +1,249 @@
+use crate::crypto::{random_salt, read_passphrase, Key, SALT_LEN};
+use crate::history::ClipboardHistoryEntry;
+use crate::store::{write_atomic, HistoryStore};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Marks an encrypted history file, followed by a format version.
+const MAGIC: &[u8] = b"ZPHIST\x01";
+const KEY_FILE_TAG: u8 = b'k';
+const PASSPHRASE_TAG: u8 = b'p';
+
+/// The passphrase and the key last derived from it, kept for the rest of the process
+/// so the passphrase is only asked for, and Argon2 only run, once.
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+static DERIVED_KEY: Mutex<Option<([u8; SALT_LEN], Key)>> = Mutex::new(None);
+
+/// Where the key of an [`EncryptedStore`] comes from.
+#[derive(Debug, Clone)]
+pub enum KeySource {
+    /// A random key in this file, created on first write.
+    File(PathBuf),
+    /// A passphrase, run through Argon2id with a salt stored in the history file.
+    Passphrase,
+}
+
+/// Uses `passphrase` for passphrase-encrypted history instead of asking for it.
+pub fn set_passphrase(passphrase: String) {
+    // Same lock order as `passphrase_key`
+    let mut derived = DERIVED_KEY.lock().unwrap();
+    *PASSPHRASE.lock().unwrap() = Some(passphrase);
+    *derived = None;
+}
+
+/// Loads the key for an existing history. A missing key file is not reported as
+/// `NotFound`, which would be taken for a missing history and start a new one.
+fn load_key_file(key_file: &Path) -> io::Result<Key> {
+    Key::load(key_file).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => io::Error::other(format!(
+            "{} is missing, so the encrypted history can't be read",
+            key_file.display()
+        )),
+        _ => e,
+    })
+}
+
+/// Stores the whole history as a JSON array encrypted with XChaCha20-Poly1305.
+///
+/// The file starts with a header saying how the key is obtained, followed for
+/// passphrases by the Argon2 salt, and then the nonce and ciphertext. Like
+/// [`super::JsonStore`], every append rewrites the file.
+pub struct EncryptedStore {
+    path: PathBuf,
+    key_source: KeySource,
+}
+
+impl EncryptedStore {
+    pub fn new(path: PathBuf, key_source: KeySource) -> Self {
+        Self { path, key_source }
+    }
+
+    fn passphrase_key(&self, salt: [u8; SALT_LEN]) -> io::Result<Key> {
+        let mut derived = DERIVED_KEY.lock().unwrap();
+        if let Some((cached_salt, key)) = derived.as_ref() {
+            if *cached_salt == salt {
+                return Ok(key.clone());
+            }
+        }
+
+        let mut passphrase = PASSPHRASE.lock().unwrap();
+        let passphrase = match passphrase.as_ref() {
+            Some(passphrase) => passphrase,
+            None => passphrase.insert(read_passphrase("Clipboard history passphrase: ")?),
+        };
+        let key = Key::derive(passphrase, &salt).map_err(io::Error::other)?;
+        *derived = Some((salt, key.clone()));
+        Ok(key)
+    }
+
+    /// Splits the file into the salt, if the key comes from a passphrase, and the
+    /// sealed history.
+    fn parse<'a>(&self, data: &'a [u8]) -> io::Result<(Option<[u8; SALT_LEN]>, &'a [u8])> {
+        let rest = data.strip_prefix(MAGIC).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an encrypted clipboard history file",
+            )
+        })?;
+        let (tag, rest) = rest.split_first().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "encrypted history is truncated",
+            )
+        })?;
+
+        match (*tag, &self.key_source) {
+            (KEY_FILE_TAG, KeySource::File(_)) => Ok((None, rest)),
+            (PASSPHRASE_TAG, KeySource::Passphrase) if rest.len() >= SALT_LEN => {
+                let (salt, sealed) = rest.split_at(SALT_LEN);
+                Ok((Some(salt.try_into().unwrap()), sealed))
+            }
+            (PASSPHRASE_TAG, KeySource::Passphrase) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "encrypted history is truncated",
+            )),
+            (KEY_FILE_TAG, KeySource::Passphrase) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the history is encrypted with a key file, but history.encryption is passphrase",
+            )),
+            (PASSPHRASE_TAG, KeySource::File(_)) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the history is encrypted with a passphrase, but history.encryption is keyfile",
+            )),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unknown key type in encrypted history",
+            )),
+        }
+    }
+}
+
+impl HistoryStore for EncryptedStore {
+    fn load(&self) -> io::Result<Vec<ClipboardHistoryEntry>> {
+        let data = fs::read(&self.path)?;
+        let (salt, sealed) = self.parse(&data)?;
+        let key = match (&self.key_source, salt) {
+            (KeySource::File(key_file), _) => load_key_file(key_file)?,
+            (KeySource::Passphrase, salt) => self.passphrase_key(salt.unwrap_or_default())?,
+        };
+
+        // Not InvalidData: a wrong key must not be mistaken for a corrupted file,
+        // which would be backed up and replaced
+        let plaintext = key.decrypt(sealed).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("wrong passphrase or key for {}", self.path.display()),
+            )
+        })?;
+        serde_json::from_slice(&plaintext)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn append(&mut self, entry: &ClipboardHistoryEntry) -> io::Result<()> {
+        let mut history = match self.load() {
+            Ok(history) => history,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        history.push(entry.clone());
+        self.replace(&history)
+    }
+
+    fn replace(&mut self, entries: &[ClipboardHistoryEntry]) -> io::Result<()> {
+        let mut data = MAGIC.to_vec();
+        let key = match &self.key_source {
+            KeySource::File(key_file) => {
+                data.push(KEY_FILE_TAG);
+                // Only a new history gets a new key; an existing one must keep its key
+                if self.path.exists() {
+                    load_key_file(key_file)?
+                } else {
+                    Key::load_or_create(key_file)?
+                }
+            }
+            KeySource::Passphrase => {
+                // Keep the salt of the existing file, so the derived key can be reused
+                let existing = fs::read(&self.path).ok();
+                let salt = existing
+                    .as_deref()
+                    .and_then(|existing| self.parse(existing).ok())
+                    .and_then(|(salt, _)| salt)
+                    .unwrap_or_else(random_salt);
+                data.push(PASSPHRASE_TAG);
+                data.extend(salt);
+                self.passphrase_key(salt)?
+            }
+        };
+
+        let plaintext = serde_json::to_vec(entries)?;
+        data.extend(key.encrypt(&plaintext).map_err(io::Error::other)?);
+        write_atomic(&self.path, &data)
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::EntrySource;
+
+    #[test]
+    fn test_key_file_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_file = dir.path().join("history.key");
+        let mut store = EncryptedStore::new(
+            dir.path().join("clipboard_history.enc"),
+            KeySource::File(key_file.clone()),
+        );
+
+        let entry = ClipboardHistoryEntry::new("hunter2".to_string(), EntrySource::Manual);
+        store.append(&entry).unwrap();
+        assert_eq!(store.load().unwrap()[0].content, "hunter2");
+        let data = fs::read(store.path()).unwrap();
+        assert!(!String::from_utf8_lossy(&data).contains("hunter2"));
+
+        // A different key is reported as such, not as a corrupted file
+        fs::remove_file(&key_file).unwrap();
+        Key::generate().create(&key_file).unwrap();
+        let err = store.load().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        let passphrase_store =
+            EncryptedStore::new(store.path().to_path_buf(), KeySource::Passphrase);
+        assert_eq!(
+            passphrase_store.load().unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_passphrase_store() {
+        let dir = tempfile::tempdir().unwrap();
+        set_passphrase("correct horse".to_string());
+        let mut store = EncryptedStore::new(
+            dir.path().join("clipboard_history.enc"),
+            KeySource::Passphrase,
+        );
+
+        store
+            .replace(&[ClipboardHistoryEntry::new(
+                "a".to_string(),
+                EntrySource::Manual,
+            )])
+            .unwrap();
+        store
+            .append(&ClipboardHistoryEntry::new(
+                "b".to_string(),
+                EntrySource::Manual,
+            ))
+            .unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[1].content, "b");
+    }
+}
@@ -0,0 +1,55 @@
+use crate::history::ClipboardHistoryEntry;
+use crate::store::{write_atomic, HistoryStore};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Stores the whole history as a single pretty-printed JSON array.
+///
+/// This is the original `clipboard_history.json` format; every append rewrites the file.
+pub struct JsonStore {
+    path: PathBuf,
+}
+
+impl JsonStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl HistoryStore for JsonStore {
+    fn load(&self) -> io::Result<Vec<ClipboardHistoryEntry>> {
+        let content = fs::read_to_string(&self.path)?;
+
+        // Ensure the JSON is valid and not empty
+        let trimmed_content = content.trim();
+        if trimmed_content.is_empty() || !trimmed_content.starts_with('[') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid or empty clipboard history file",
+            ));
+        }
+
+        serde_json::from_str(trimmed_content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn append(&mut self, entry: &ClipboardHistoryEntry) -> io::Result<()> {
+        let mut history = match self.load() {
+            Ok(history) => history,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        history.push(entry.clone());
+        self.replace(&history)
+    }
+
+    fn replace(&mut self, entries: &[ClipboardHistoryEntry]) -> io::Result<()> {
+        let serialized_history = serde_json::to_string_pretty(entries)?;
+        write_atomic(&self.path, serialized_history.as_bytes())
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
@@ -0,0 +1,79 @@
+use crate::history::ClipboardHistoryEntry;
+use crate::store::{restrict_permissions, write_atomic, HistoryStore};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Stores one JSON entry per line so that copies only append to the file.
+pub struct JsonlStore {
+    path: PathBuf,
+}
+
+impl JsonlStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl HistoryStore for JsonlStore {
+    fn load(&self) -> io::Result<Vec<ClipboardHistoryEntry>> {
+        let content = fs::read_to_string(&self.path)?;
+
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            })
+            .collect()
+    }
+
+    fn append(&mut self, entry: &ClipboardHistoryEntry) -> io::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+        restrict_permissions(&file)?;
+
+        // Start on a fresh line if a previous write was cut short
+        if file.metadata()?.len() > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                line.insert(0, '\n');
+            }
+        }
+
+        file.write_all(line.as_bytes())
+    }
+
+    fn replace(&mut self, entries: &[ClipboardHistoryEntry]) -> io::Result<()> {
+        let mut content = String::new();
+        for entry in entries {
+            content.push_str(&serde_json::to_string(entry)?);
+            content.push('\n');
+        }
+        write_atomic(&self.path, content.as_bytes())
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn salvage(&self) -> Vec<ClipboardHistoryEntry> {
+        fs::read(&self.path)
+            .map(|bytes| {
+                String::from_utf8_lossy(&bytes)
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
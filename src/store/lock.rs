@@ -0,0 +1,46 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+/// Advisory lock serialising access to the history between the CLI, the
+/// monitoring daemon and the sync daemon.
+///
+/// The lock is held on `clipboard_history.lock` in the history directory and is
+/// released when the guard is dropped.
+pub struct HistoryLock {
+    file: File,
+}
+
+impl HistoryLock {
+    /// Blocks until an exclusive lock is held, for read-modify-write cycles.
+    pub fn exclusive(dir: &Path) -> io::Result<Self> {
+        let file = Self::open(dir)?;
+        file.lock()?;
+        Ok(Self { file })
+    }
+
+    /// Blocks until a shared lock is held, for plain reads.
+    pub fn shared(dir: &Path) -> io::Result<Self> {
+        let file = Self::open(dir)?;
+        file.lock_shared()?;
+        Ok(Self { file })
+    }
+
+    fn open(dir: &Path) -> io::Result<File> {
+        if !dir.exists() {
+            fs::create_dir_all(dir)?;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join("clipboard_history.lock"))
+    }
+}
+
+impl Drop for HistoryLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
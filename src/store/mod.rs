@@ -0,0 +1,286 @@
+pub mod encrypted;
+pub mod json;
+pub mod jsonl;
+pub mod lock;
+pub mod sqlite;
+
+use crate::config::{zp_dir, HistoryBackend, HistoryConfig, HistoryEncryption, ZpConfig};
+use crate::error::ZpError;
+use crate::history::ClipboardHistoryEntry;
+use chrono::Local;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub use encrypted::{EncryptedStore, KeySource};
+pub use json::JsonStore;
+pub use jsonl::JsonlStore;
+pub use lock::HistoryLock;
+pub use sqlite::SqliteStore;
+
+/// Storage backend for clipboard history.
+///
+/// Entries are always returned oldest first, matching the order they were appended in.
+pub trait HistoryStore {
+    /// Loads every stored entry.
+    fn load(&self) -> io::Result<Vec<ClipboardHistoryEntry>>;
+
+    /// Adds a single entry to the end of the history.
+    fn append(&mut self, entry: &ClipboardHistoryEntry) -> io::Result<()>;
+
+    /// Replaces the whole history with `entries`.
+    fn replace(&mut self, entries: &[ClipboardHistoryEntry]) -> io::Result<()>;
+
+    /// Path of the file backing this store.
+    fn path(&self) -> &Path;
+
+    /// Reads whatever entries are still intact after `load` reported corrupted data.
+    fn salvage(&self) -> Vec<ClipboardHistoryEntry> {
+        vec![]
+    }
+}
+
+/// Returns true for errors caused by unreadable history contents rather than IO failures.
+pub fn is_corrupted(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+    )
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it into place,
+/// so readers never observe a partially written file.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let mut file = fs::File::create(&tmp_path)?;
+    restrict_permissions(&file)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Makes `file` readable and writable only by its owner, whatever the umask.
+pub fn restrict_permissions(file: &fs::File) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    #[cfg(not(unix))]
+    let _ = file;
+    Ok(())
+}
+
+/// Makes `dir` accessible only by its owner.
+pub fn restrict_dir_permissions(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// Copies a corrupted history file to `<name>.<timestamp>.bak` and returns the backup path.
+pub fn backup_corrupted(path: &Path) -> io::Result<PathBuf> {
+    let mut backup_name = path.as_os_str().to_owned();
+    backup_name.push(format!(".{}.bak", Local::now().format("%Y%m%d%H%M%S")));
+    let backup_path = PathBuf::from(backup_name);
+
+    fs::copy(path, &backup_path)?;
+    Ok(backup_path)
+}
+
+/// Loads the history from `store`, recovering from a corrupted file.
+///
+/// A missing file is treated as an empty history. If the contents can't be parsed,
+/// the file is backed up and any entries that can still be read are returned, so
+/// the next write doesn't silently throw the old history away.
+pub fn load_or_recover(store: &dyn HistoryStore) -> io::Result<Vec<ClipboardHistoryEntry>> {
+    match store.load() {
+        Ok(entries) => Ok(entries),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) if is_corrupted(&e) => {
+            let backup = backup_corrupted(store.path())?;
+            let entries = store.salvage();
+            eprintln!(
+                "Warning: clipboard history is corrupted ({}). Backed it up to {} and kept {} readable entries.",
+                e,
+                backup.display(),
+                entries.len()
+            );
+            Ok(entries)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// File holding the history when it is encrypted, whatever the backend.
+pub const ENCRYPTED_FILE_NAME: &str = "clipboard_history.enc";
+
+impl HistoryBackend {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            HistoryBackend::Json => "clipboard_history.json",
+            HistoryBackend::Jsonl => "clipboard_history.jsonl",
+            HistoryBackend::Sqlite => "clipboard_history.db",
+        }
+    }
+}
+
+/// Opens the store for `backend` inside `dir`, creating the directory if needed.
+///
+/// When a non-JSON backend is opened for the first time and a legacy
+/// `clipboard_history.json` exists next to it, its entries are imported.
+pub fn open_store(dir: &Path, backend: HistoryBackend) -> io::Result<Box<dyn HistoryStore>> {
+    if !dir.exists() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let path = dir.join(backend.file_name());
+    let is_new = !path.exists();
+
+    let mut store: Box<dyn HistoryStore> = match backend {
+        HistoryBackend::Json => return Ok(Box::new(JsonStore::new(path))),
+        HistoryBackend::Jsonl => Box::new(JsonlStore::new(path)),
+        HistoryBackend::Sqlite => Box::new(SqliteStore::open(path)?),
+    };
+
+    let legacy = JsonStore::new(dir.join(HistoryBackend::Json.file_name()));
+    if is_new && legacy.path().exists() {
+        store.replace(&load_or_recover(&legacy)?)?;
+    }
+
+    Ok(store)
+}
+
+/// Opens the store for the history settings in `config`: the encrypted store when
+/// encryption is enabled, and the backend's store otherwise.
+pub fn open_configured_store(
+    dir: &Path,
+    config: &HistoryConfig,
+) -> io::Result<Box<dyn HistoryStore>> {
+    let key_source = match config.encryption {
+        HistoryEncryption::None => return open_store(dir, config.backend),
+        HistoryEncryption::Keyfile => KeySource::File(
+            config
+                .key_file
+                .clone()
+                .unwrap_or_else(|| dir.join("history.key")),
+        ),
+        HistoryEncryption::Passphrase => KeySource::Passphrase,
+    };
+
+    if !dir.exists() {
+        fs::create_dir_all(dir)?;
+    }
+    Ok(Box::new(EncryptedStore::new(
+        dir.join(ENCRYPTED_FILE_NAME),
+        key_source,
+    )))
+}
+
+/// Opens the store selected in `~/.zp/config.json`.
+///
+/// A broken config file is an error rather than falling back to the defaults, which
+/// would write an encrypted history out in plain text.
+pub fn open_default_store() -> Result<Box<dyn HistoryStore>, ZpError> {
+    let config = ZpConfig::load()?;
+    Ok(open_configured_store(&zp_dir(), &config.history)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::EntrySource;
+
+    fn entry(content: &str) -> ClipboardHistoryEntry {
+        ClipboardHistoryEntry::new(content.to_string(), EntrySource::Manual)
+    }
+
+    fn round_trip(backend: HistoryBackend) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = open_store(dir.path(), backend).unwrap();
+
+        store.append(&entry("first")).unwrap();
+        store.append(&entry("second\nwith newline")).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].content, "first");
+        assert_eq!(loaded[1].content, "second\nwith newline");
+
+        store.replace(&[entry("only")]).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].content, "only");
+    }
+
+    #[test]
+    fn test_json_store() {
+        round_trip(HistoryBackend::Json);
+    }
+
+    #[test]
+    fn test_jsonl_store() {
+        round_trip(HistoryBackend::Jsonl);
+    }
+
+    #[test]
+    fn test_sqlite_store() {
+        round_trip(HistoryBackend::Sqlite);
+    }
+
+    #[test]
+    fn test_recovers_corrupted_json_with_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(dir.path(), HistoryBackend::Json).unwrap();
+        fs::write(store.path(), "[{\"content\": \"trunc").unwrap();
+
+        assert!(is_corrupted(&store.load().unwrap_err()));
+        assert!(load_or_recover(store.as_ref()).unwrap().is_empty());
+
+        let backups: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".bak"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+    }
+
+    #[test]
+    fn test_salvages_intact_jsonl_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = open_store(dir.path(), HistoryBackend::Jsonl).unwrap();
+        store.append(&entry("kept")).unwrap();
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(store.path())
+            .unwrap();
+        file.write_all(b"{\"content\": \"trunc").unwrap();
+
+        let recovered = load_or_recover(store.as_ref()).unwrap();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].content, "kept");
+
+        // Appending after a torn write must not glue onto the partial line
+        store.append(&entry("next")).unwrap();
+        assert_eq!(store.salvage().len(), 2);
+    }
+
+    #[test]
+    fn test_imports_legacy_json_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut legacy = open_store(dir.path(), HistoryBackend::Json).unwrap();
+        legacy.append(&entry("legacy")).unwrap();
+
+        let store = open_store(dir.path(), HistoryBackend::Jsonl).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].content, "legacy");
+    }
+}
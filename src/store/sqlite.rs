@@ -0,0 +1,83 @@
+use crate::history::ClipboardHistoryEntry;
+use crate::store::{restrict_permissions, HistoryStore};
+use rusqlite::{params, Connection};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Stores history in an SQLite database, one row per entry.
+///
+/// Entries are kept as serialized JSON so new fields don't need schema migrations.
+pub struct SqliteStore {
+    path: PathBuf,
+    conn: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let conn = Connection::open(&path).map_err(io::Error::other)?;
+        restrict_permissions(&File::open(&path)?)?;
+        conn.busy_timeout(Duration::from_secs(5))
+            .map_err(io::Error::other)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS entries (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                entry TEXT NOT NULL
+            );",
+        )
+        .map_err(io::Error::other)?;
+        Ok(Self { path, conn })
+    }
+}
+
+fn insert(conn: &Connection, entry: &ClipboardHistoryEntry) -> io::Result<()> {
+    let serialized = serde_json::to_string(entry)?;
+    conn.execute(
+        "INSERT INTO entries (timestamp, entry) VALUES (?1, ?2)",
+        params![entry.timestamp, serialized],
+    )
+    .map_err(io::Error::other)?;
+    Ok(())
+}
+
+impl HistoryStore for SqliteStore {
+    fn load(&self) -> io::Result<Vec<ClipboardHistoryEntry>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT entry FROM entries ORDER BY seq")
+            .map_err(io::Error::other)?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(io::Error::other)?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let serialized = row.map_err(io::Error::other)?;
+            entries.push(
+                serde_json::from_str(&serialized)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            );
+        }
+        Ok(entries)
+    }
+
+    fn append(&mut self, entry: &ClipboardHistoryEntry) -> io::Result<()> {
+        insert(&self.conn, entry)
+    }
+
+    fn replace(&mut self, entries: &[ClipboardHistoryEntry]) -> io::Result<()> {
+        let tx = self.conn.transaction().map_err(io::Error::other)?;
+        tx.execute("DELETE FROM entries", [])
+            .map_err(io::Error::other)?;
+        for entry in entries {
+            insert(&tx, entry)?;
+        }
+        tx.commit().map_err(io::Error::other)
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
@@ -1,4 +1,5 @@
 use crate::config::SyncConfig;
+use crate::error::ZpError;
 use crate::sync::{protocol::SyncProtocol, server::SyncServer};
 
 use std::env;
@@ -13,12 +14,12 @@ pub struct SyncHandler {
 }
 
 impl SyncHandler {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new() -> Result<Self, ZpError> {
         let config = SyncConfig::load()?;
         Ok(Self { config })
     }
 
-    pub async fn start_daemon(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn start_daemon(&self) -> Result<(), ZpError> {
         if !self.config.enabled {
             println!("🔕 Sync is disabled in configuration");
             return Ok(());
@@ -81,7 +82,7 @@ impl SyncHandler {
                 }
                 Err(e) => {
                     eprintln!("Error starting sync daemon: {}", e);
-                    Err(e.into())
+                    Err(ZpError::Io(std::io::Error::other(e)))
                 }
             }
         }
@@ -97,7 +98,7 @@ impl SyncHandler {
     }
 
     // The actual sync daemon worker process
-    async fn run_sync_daemon_worker(&self) -> Result<(), Box<dyn std::error::Error>> {
+    async fn run_sync_daemon_worker(&self) -> Result<(), ZpError> {
         // Get the pid file path
         let pid_dir = env::var("HOME")
             .map(|home| PathBuf::from(home).join(".zp"))
@@ -161,7 +162,7 @@ impl SyncHandler {
         Ok(())
     }
 
-    pub async fn sync_now(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn sync_now(&self) -> Result<(), ZpError> {
         if !self.config.enabled {
             println!("🔕 Sync is disabled in configuration");
             return Ok(());
@@ -194,32 +195,28 @@ impl SyncHandler {
         }
     }
 
-    pub fn enable_sync(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn enable_sync(&mut self) -> Result<(), ZpError> {
         self.config.enabled = true;
         self.config.save()?;
         println!("✅ Sync enabled");
         Ok(())
     }
 
-    pub fn disable_sync(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn disable_sync(&mut self) -> Result<(), ZpError> {
         self.config.enabled = false;
         self.config.save()?;
         println!("🔕 Sync disabled");
         Ok(())
     }
 
-    pub fn add_peer(
-        &mut self,
-        peer_id: String,
-        endpoint: String,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn add_peer(&mut self, peer_id: String, endpoint: String) -> Result<(), ZpError> {
         self.config.add_peer(peer_id.clone(), endpoint.clone());
         self.config.save()?;
         println!("➕ Added peer: {} -> {}", peer_id, endpoint);
         Ok(())
     }
 
-    pub fn remove_peer(&mut self, peer_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn remove_peer(&mut self, peer_id: &str) -> Result<(), ZpError> {
         if self.config.peers.remove(peer_id).is_some() {
             self.config.save()?;
             println!("➖ Removed peer: {}", peer_id);
@@ -229,11 +226,7 @@ impl SyncHandler {
         Ok(())
     }
 
-    pub fn set_peer_enabled(
-        &mut self,
-        peer_id: &str,
-        enabled: bool,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn set_peer_enabled(&mut self, peer_id: &str, enabled: bool) -> Result<(), ZpError> {
         if let Some(peer) = self.config.peers.get_mut(peer_id) {
             peer.enabled = enabled;
             self.config.save()?;
@@ -245,10 +238,7 @@ impl SyncHandler {
         Ok(())
     }
 
-    pub async fn test_peer_connection(
-        &self,
-        peer_id: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn test_peer_connection(&self, peer_id: &str) -> Result<(), ZpError> {
         if let Some(peer_config) = self.config.peers.get(peer_id) {
             let protocol = SyncProtocol::new(self.config.clone());
             let endpoint = protocol.resolve_endpoint_public(peer_config).await?;
@@ -278,7 +268,7 @@ impl SyncHandler {
         Ok(())
     }
 
-    pub fn stop_sync_daemon() -> Result<(), Box<dyn std::error::Error>> {
+    pub fn stop_sync_daemon() -> Result<(), ZpError> {
         let pid_dir = env::var("HOME")
             .map(|home| PathBuf::from(home).join(".zp"))
             .unwrap_or_else(|_| PathBuf::from(".zp"));
@@ -331,7 +321,7 @@ impl SyncHandler {
         Ok(())
     }
 
-    pub fn sync_daemon_status() -> Result<(), Box<dyn std::error::Error>> {
+    pub fn sync_daemon_status() -> Result<(), ZpError> {
         let pid_dir = env::var("HOME")
             .map(|home| PathBuf::from(home).join(".zp"))
             .unwrap_or_else(|_| PathBuf::from(".zp"));
@@ -2,10 +2,17 @@ pub mod handler;
 pub mod protocol;
 pub mod server;
 
-use crate::config::SyncConfig;
-use crate::history::ClipboardHistoryEntry;
+use crate::config::{DedupStrategy, HistoryConfig, SyncConfig, ZpConfig};
+use crate::dedup::find_duplicate;
+use crate::error::ZpError;
+use crate::history::{
+    load_clipboard_history, parse_entry_time, update_clipboard_history, ClipboardHistoryEntry,
+    EntrySource,
+};
+use crate::retention::{apply_retention, is_retained};
+use crate::secrets::shareable;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SyncMessage {
@@ -85,3 +92,186 @@ pub fn create_sync_message(
         data,
     }
 }
+
+/// Loads the entries to send to a peer that last synced at `since_timestamp`.
+///
+/// Image data lives in local blobs and isn't synced, and entries go through
+/// [`shareable`] so that secrets don't leave this machine. The history lock and
+/// file IO block, so they run on tokio's blocking thread pool.
+pub async fn entries_to_share(since_timestamp: i64) -> Result<Vec<ClipboardHistoryEntry>, ZpError> {
+    run_blocking(move || {
+        let secrets = ZpConfig::load()?.secrets;
+        let entries = load_clipboard_history()?
+            .into_iter()
+            .filter(|entry| !entry.is_image())
+            .filter(|entry| {
+                // Entries with invalid timestamps are skipped
+                chrono::DateTime::parse_from_rfc3339(&entry.timestamp)
+                    .is_ok_and(|dt| dt.timestamp() > since_timestamp)
+            })
+            .filter_map(|entry| shareable(entry, &secrets))
+            .collect();
+        Ok(entries)
+    })
+    .await
+}
+
+/// Merges the `remote` entries received from `from_peer` into the history with
+/// [`merge_with_retention`], under the history lock so concurrent copies aren't lost.
+/// Returns the number of new entries.
+pub async fn merge_into_history(
+    remote: Vec<ClipboardHistoryEntry>,
+    from_peer: String,
+) -> Result<usize, ZpError> {
+    run_blocking(move || {
+        let config = ZpConfig::load()?.history;
+        update_clipboard_history(|local| merge_with_retention(local, remote, &from_peer, &config))
+    })
+    .await
+}
+
+async fn run_blocking<T, F>(f: F) -> Result<T, ZpError>
+where
+    F: FnOnce() -> Result<T, ZpError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| ZpError::Sync(format!("history task failed: {}", e)))?
+}
+
+/// Adds the `remote` entries received from `from_peer` that `local` doesn't have yet,
+/// keeping the result ordered by timestamp. Returns the number of entries added.
+///
+/// Entries are matched by id; imported entries are marked as coming from sync and
+/// keep the peer they were originally recorded on.
+pub fn merge_entries(
+    local: &mut Vec<ClipboardHistoryEntry>,
+    remote: Vec<ClipboardHistoryEntry>,
+    from_peer: &str,
+) -> usize {
+    let mut existing_ids: HashSet<String> = local.iter().map(|entry| entry.id.clone()).collect();
+
+    let mut num_new = 0;
+    for mut entry in remote {
+        entry.normalize();
+        if existing_ids.insert(entry.id.clone()) {
+            entry.source = EntrySource::Sync;
+            entry.peer_id.get_or_insert_with(|| from_peer.to_string());
+            local.push(entry);
+            num_new += 1;
+        }
+    }
+
+    if num_new > 0 {
+        local.sort_by(|a, b| {
+            let ts_a = chrono::DateTime::parse_from_rfc3339(&a.timestamp).unwrap_or_default();
+            let ts_b = chrono::DateTime::parse_from_rfc3339(&b.timestamp).unwrap_or_default();
+            ts_a.cmp(&ts_b)
+        });
+    }
+
+    num_new
+}
+
+/// Merges `remote` into `local` like [`merge_entries`], then applies the retention
+/// policy from `config` so that entries pruned here aren't brought back by peers that
+/// still hold them. Returns the number of new entries that were kept.
+///
+/// With global deduplication, remote entries whose content is already in `local`
+/// only refresh the local entry's timestamp instead of being added next to it.
+pub fn merge_with_retention(
+    local: &mut Vec<ClipboardHistoryEntry>,
+    remote: Vec<ClipboardHistoryEntry>,
+    from_peer: &str,
+    config: &HistoryConfig,
+) -> usize {
+    let now = chrono::Local::now();
+    let policy = &config.retention;
+    let known_ids: HashSet<String> = local.iter().map(|entry| entry.id.clone()).collect();
+
+    let mut refreshed = false;
+    let mut new_entries = vec![];
+    for mut entry in remote {
+        // Peers don't send image blobs, so image entries would have nothing to show
+        if entry.is_image() || !is_retained(&entry, policy, now) {
+            continue;
+        }
+        entry.normalize();
+        if config.dedup == DedupStrategy::Global && !known_ids.contains(&entry.id) {
+            if let Some(position) = find_duplicate(local, &entry, DedupStrategy::Global) {
+                let existing = &mut local[position];
+                if parse_entry_time(&entry.timestamp) > parse_entry_time(&existing.timestamp) {
+                    existing.timestamp = entry.timestamp;
+                    refreshed = true;
+                }
+                continue;
+            }
+        }
+        new_entries.push(entry);
+    }
+
+    if merge_entries(local, new_entries, from_peer) == 0 && refreshed {
+        local.sort_by_key(|entry| parse_entry_time(&entry.timestamp));
+    }
+    apply_retention(local, policy, now);
+
+    local
+        .iter()
+        .filter(|entry| !known_ids.contains(&entry.id))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_entries_by_id() {
+        let shared = ClipboardHistoryEntry::new("shared".to_string(), EntrySource::Manual);
+        let mut local = vec![shared.clone()];
+        let mut remote_only = ClipboardHistoryEntry::new("shared".to_string(), EntrySource::Manual);
+        remote_only.peer_id = None;
+
+        let added = merge_entries(&mut local, vec![shared, remote_only], "laptop");
+        assert_eq!(added, 1);
+        assert_eq!(local.len(), 2);
+        assert_eq!(local[1].source, EntrySource::Sync);
+        assert_eq!(local[1].peer_id.as_deref(), Some("laptop"));
+    }
+
+    #[test]
+    fn test_merge_does_not_restore_pruned_entries() {
+        let old = ClipboardHistoryEntry::new("old".to_string(), EntrySource::Manual);
+        let new = ClipboardHistoryEntry::new("new".to_string(), EntrySource::Manual);
+        let mut local = vec![new];
+        let mut config = HistoryConfig::default();
+        config.retention.max_entries = Some(1);
+
+        // "old" was pruned locally but the peer still has it
+        let added = merge_with_retention(&mut local, vec![old], "laptop", &config);
+        assert_eq!(added, 0);
+        assert_eq!(local.len(), 1);
+        assert_eq!(local[0].content, "new");
+    }
+
+    #[test]
+    fn test_merge_with_global_dedup() {
+        let mut local = vec![
+            ClipboardHistoryEntry::new("a".to_string(), EntrySource::Manual),
+            ClipboardHistoryEntry::new("b".to_string(), EntrySource::Manual),
+        ];
+        let local_id = local[0].id.clone();
+        let remote = ClipboardHistoryEntry::new("a".to_string(), EntrySource::Manual);
+        let config = HistoryConfig {
+            dedup: DedupStrategy::Global,
+            ..Default::default()
+        };
+
+        let added = merge_with_retention(&mut local, vec![remote], "laptop", &config);
+        assert_eq!(added, 0);
+        assert_eq!(local.len(), 2);
+        // The local copy of "a" took the newer timestamp and moved to the end
+        assert_eq!(local[1].id, local_id);
+    }
+}
@@ -1,6 +1,10 @@
 use crate::config::{PeerConfig, SyncConfig};
-use crate::history::{load_clipboard_history, ClipboardHistoryEntry};
-use crate::sync::{create_sync_message, SyncData, SyncManager, SyncMessage, SyncMessageType};
+use crate::error::ZpError;
+use crate::history::ClipboardHistoryEntry;
+use crate::sync::{
+    create_sync_message, entries_to_share, merge_into_history, SyncData, SyncManager, SyncMessage,
+    SyncMessageType,
+};
 
 use std::process::Command;
 use std::time::Duration;
@@ -16,7 +20,7 @@ impl SyncProtocol {
         }
     }
 
-    pub async fn sync_with_peers(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn sync_with_peers(&mut self) -> Result<(), ZpError> {
         if !self.manager.is_enabled() {
             return Ok(());
         }
@@ -42,7 +46,7 @@ impl SyncProtocol {
         &mut self,
         peer_id: &str,
         peer_config: &PeerConfig,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), ZpError> {
         let endpoint = self.resolve_endpoint(peer_config).await?;
 
         println!("🔗 Syncing with peer {} at {}", peer_id, endpoint);
@@ -62,11 +66,11 @@ impl SyncProtocol {
                 remote_entries.len(),
                 peer_id
             );
-            self.merge_remote_entries(remote_entries).await?;
+            self.merge_remote_entries(remote_entries, peer_id).await?;
         }
 
         // Send our local entries that are newer than peer's last sync
-        let local_entries = self.get_local_entries_since(last_sync)?;
+        let local_entries = entries_to_share(last_sync).await?;
         if !local_entries.is_empty() {
             println!("📤 Sending {} entries to {}", local_entries.len(), peer_id);
             self.send_entries(&endpoint, local_entries).await?;
@@ -81,10 +85,7 @@ impl SyncProtocol {
         Ok(())
     }
 
-    async fn resolve_endpoint(
-        &self,
-        peer_config: &PeerConfig,
-    ) -> Result<String, Box<dyn std::error::Error>> {
+    async fn resolve_endpoint(&self, peer_config: &PeerConfig) -> Result<String, ZpError> {
         if let Some(ssh_config) = &peer_config.ssh_config {
             // Set up SSH tunnel if needed
             self.setup_ssh_tunnel(ssh_config).await?;
@@ -94,10 +95,7 @@ impl SyncProtocol {
         }
     }
 
-    async fn setup_ssh_tunnel(
-        &self,
-        ssh_config: &crate::config::SshConfig,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    async fn setup_ssh_tunnel(&self, ssh_config: &crate::config::SshConfig) -> Result<(), ZpError> {
         // Check if tunnel is already running
         if self.is_port_in_use(ssh_config.tunnel_local_port) {
             return Ok(()); // Tunnel already exists
@@ -136,7 +134,7 @@ impl SyncProtocol {
         std::net::TcpListener::bind(format!("127.0.0.1:{}", port)).is_err()
     }
 
-    async fn handshake(&self, endpoint: &str) -> Result<(), Box<dyn std::error::Error>> {
+    async fn handshake(&self, endpoint: &str) -> Result<(), ZpError> {
         let client = reqwest::Client::new();
         let handshake_msg = create_sync_message(
             SyncMessageType::Handshake,
@@ -155,7 +153,10 @@ impl SyncProtocol {
             let _response_msg: SyncMessage = response.json().await?;
             Ok(())
         } else {
-            Err(format!("Handshake failed with status: {}", response.status()).into())
+            Err(ZpError::Sync(format!(
+                "Handshake failed with status: {}",
+                response.status()
+            )))
         }
     }
 
@@ -163,7 +164,7 @@ impl SyncProtocol {
         &self,
         endpoint: &str,
         since_timestamp: i64,
-    ) -> Result<Vec<ClipboardHistoryEntry>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<ClipboardHistoryEntry>, ZpError> {
         let client = reqwest::Client::new();
 
         let response = client
@@ -177,7 +178,10 @@ impl SyncProtocol {
             let entries: Vec<ClipboardHistoryEntry> = response.json().await?;
             Ok(entries)
         } else {
-            Err(format!("Failed to request history: {}", response.status()).into())
+            Err(ZpError::Sync(format!(
+                "Failed to request history: {}",
+                response.status()
+            )))
         }
     }
 
@@ -185,7 +189,7 @@ impl SyncProtocol {
         &self,
         endpoint: &str,
         entries: Vec<ClipboardHistoryEntry>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), ZpError> {
         let client = reqwest::Client::new();
         let sync_msg = create_sync_message(
             SyncMessageType::ClipboardSync,
@@ -203,77 +207,34 @@ impl SyncProtocol {
         if response.status().is_success() {
             Ok(())
         } else {
-            Err(format!("Failed to send entries: {}", response.status()).into())
+            Err(ZpError::Sync(format!(
+                "Failed to send entries: {}",
+                response.status()
+            )))
         }
     }
 
-    fn get_local_entries_since(
-        &self,
-        since_timestamp: i64,
-    ) -> Result<Vec<ClipboardHistoryEntry>, Box<dyn std::error::Error>> {
-        let entries = load_clipboard_history()?;
-
-        let filtered_entries = entries
-            .into_iter()
-            .filter(|entry| {
-                if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(&entry.timestamp) {
-                    dt.timestamp() > since_timestamp
-                } else {
-                    false
-                }
-            })
-            .collect();
-
-        Ok(filtered_entries)
-    }
-
     async fn merge_remote_entries(
         &self,
         remote_entries: Vec<ClipboardHistoryEntry>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // Load local history
-        let local_entries = load_clipboard_history().unwrap_or_else(|_| vec![]);
-
-        // Create a set of existing content to avoid duplicates
-        let existing_content: std::collections::HashSet<String> = local_entries
-            .iter()
-            .map(|entry| entry.content.clone())
-            .collect();
-
-        // Filter out entries that already exist locally
-        let new_entries: Vec<ClipboardHistoryEntry> = remote_entries
-            .into_iter()
-            .filter(|entry| !existing_content.contains(&entry.content))
-            .collect();
-
-        if !new_entries.is_empty() {
-            // Combine and sort all entries by timestamp
-            let mut all_entries = local_entries;
-            all_entries.extend(new_entries.clone());
-
-            all_entries.sort_by(|a, b| {
-                let ts_a = chrono::DateTime::parse_from_rfc3339(&a.timestamp).unwrap_or_default();
-                let ts_b = chrono::DateTime::parse_from_rfc3339(&b.timestamp).unwrap_or_default();
-                ts_a.cmp(&ts_b)
-            });
-
-            let num_new = new_entries.len();
-            // Save merged history
-            crate::sync::server::save_merged_history(all_entries)?;
+        from_peer: &str,
+    ) -> Result<(), ZpError> {
+        let num_new = merge_into_history(remote_entries, from_peer.to_string()).await?;
+        if num_new > 0 {
             println!("📋 Merged {} new clipboard entries", num_new);
         }
 
         Ok(())
     }
 
-    pub async fn sync_once(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn sync_once(&mut self) -> Result<(), ZpError> {
         self.sync_with_peers().await
     }
 
     pub async fn resolve_endpoint_public(
         &self,
         peer_config: &PeerConfig,
-    ) -> Result<String, Box<dyn std::error::Error>> {
+    ) -> Result<String, ZpError> {
         if let Some(ssh_config) = &peer_config.ssh_config {
             // Set up SSH tunnel if needed
             self.setup_ssh_tunnel(ssh_config).await?;
@@ -1,6 +1,10 @@
 use crate::config::SyncConfig;
-use crate::history::{load_clipboard_history, ClipboardHistoryEntry};
-use crate::sync::{create_sync_message, SyncData, SyncMessage, SyncMessageType};
+use crate::error::ZpError;
+use crate::history::ClipboardHistoryEntry;
+use crate::sync::{
+    create_sync_message, entries_to_share, merge_into_history, SyncData, SyncMessage,
+    SyncMessageType,
+};
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::sync::Arc;
@@ -22,7 +26,7 @@ impl SyncServer {
         }
     }
 
-    pub async fn start(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn start(&self) -> Result<(), ZpError> {
         let port = self.config.listen_port;
         let peer_id = self.config.peer_id.clone();
         let sync_state = self.sync_state.clone();
@@ -96,7 +100,7 @@ async fn handle_sync_request(
                 );
 
                 // Merge received entries with local history
-                if let Err(e) = merge_clipboard_entries(entries).await {
+                if let Err(e) = merge_clipboard_entries(entries, &message.peer_id).await {
                     eprintln!("Failed to merge clipboard entries: {}", e);
                 }
 
@@ -130,10 +134,8 @@ async fn handle_sync_request(
                 })
                 .unwrap_or(0);
 
-            match load_clipboard_history() {
-                Ok(entries) => {
-                    let filtered_entries = filter_entries_since_timestamp(entries, since_timestamp);
-
+            match entries_to_share(since_timestamp).await {
+                Ok(filtered_entries) => {
                     let response = create_sync_message(
                         SyncMessageType::HistoryResponse,
                         get_local_peer_id(),
@@ -165,11 +167,8 @@ async fn handle_history_request(
         .and_then(|s| s.parse::<i64>().ok())
         .unwrap_or(0);
 
-    match load_clipboard_history() {
-        Ok(entries) => {
-            let filtered_entries = filter_entries_since_timestamp(entries, since_timestamp);
-            Ok(warp::reply::json(&filtered_entries))
-        }
+    match entries_to_share(since_timestamp).await {
+        Ok(filtered_entries) => Ok(warp::reply::json(&filtered_entries)),
         Err(e) => {
             eprintln!("Failed to load clipboard history: {}", e);
             Ok(warp::reply::json(
@@ -179,85 +178,18 @@ async fn handle_history_request(
     }
 }
 
-fn filter_entries_since_timestamp(
-    entries: Vec<ClipboardHistoryEntry>,
-    since_timestamp: i64,
-) -> Vec<ClipboardHistoryEntry> {
-    entries
-        .into_iter()
-        .filter(|entry| {
-            if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(&entry.timestamp) {
-                dt.timestamp() > since_timestamp
-            } else {
-                false // Skip entries with invalid timestamps
-            }
-        })
-        .collect()
-}
-
 async fn merge_clipboard_entries(
     remote_entries: Vec<ClipboardHistoryEntry>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Load local history
-    let local_entries = load_clipboard_history().unwrap_or_else(|_| vec![]);
-
-    // Create a set of existing content with timestamps to avoid duplicates
-    let mut existing_entries: std::collections::HashMap<String, String> = local_entries
-        .iter()
-        .map(|entry| (entry.content.clone(), entry.timestamp.clone()))
-        .collect();
-
-    // Collect new entries that don't already exist
-    let mut new_entries = Vec::new();
-    for entry in remote_entries {
-        if !existing_entries.contains_key(&entry.content) {
-            existing_entries.insert(entry.content.clone(), entry.timestamp.clone());
-            new_entries.push(entry);
-        }
-    }
-
-    let num_new = new_entries.len();
-    if !new_entries.is_empty() {
-        // Combine all entries and sort by timestamp
-        let mut all_entries = local_entries;
-        all_entries.extend(new_entries);
-
-        all_entries.sort_by(|a, b| {
-            let ts_a = chrono::DateTime::parse_from_rfc3339(&a.timestamp).unwrap_or_default();
-            let ts_b = chrono::DateTime::parse_from_rfc3339(&b.timestamp).unwrap_or_default();
-            ts_a.cmp(&ts_b)
-        });
-
-        // Save merged history
-        save_merged_history(all_entries)?;
+    from_peer: &str,
+) -> Result<(), ZpError> {
+    let num_new = merge_into_history(remote_entries, from_peer.to_string()).await?;
+    if num_new > 0 {
         println!("✅ Merged clipboard history with {} new entries", num_new);
     }
 
     Ok(())
 }
 
-pub fn save_merged_history(
-    entries: Vec<ClipboardHistoryEntry>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    use std::env;
-    use std::fs;
-    use std::path::PathBuf;
-
-    let history_dir = env::var("HOME")
-        .map(|home| PathBuf::from(home).join(".zp"))
-        .unwrap_or_else(|_| PathBuf::from(".zp"));
-
-    if !history_dir.exists() {
-        fs::create_dir_all(&history_dir)?;
-    }
-
-    let history_file = history_dir.join("clipboard_history.json");
-    let serialized_history = serde_json::to_string_pretty(&entries)?;
-    fs::write(&history_file, serialized_history)?;
-
-    Ok(())
-}
-
 fn get_local_peer_id() -> String {
     // Try to load from config, fallback to generating one
     crate::config::SyncConfig::load()
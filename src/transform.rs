@@ -0,0 +1,266 @@
+use crate::concat::{code_fence, language_tag};
+use crate::encoding::{encode_binary, BinaryFormat};
+use crate::error::ZpError;
+use clap::ValueEnum;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// A step applied to text before it is copied.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    /// Remove leading and trailing whitespace
+    Trim,
+    /// Remove the indentation shared by all non-blank lines
+    Dedent,
+    /// Remove ANSI escape sequences such as colors
+    StripAnsi,
+    /// Collapse runs of spaces and tabs, and runs of blank lines, into one
+    Collapse,
+    /// Join the non-blank lines with single spaces
+    Join,
+    /// Wrap in a Markdown code block tagged with the detected language
+    Fence,
+    /// Quote as a single POSIX shell word
+    ShellQuote,
+    /// Escape for use inside a JSON string
+    JsonEscape,
+    /// Encode the UTF-8 bytes as base64
+    Base64,
+    /// Percent-encode everything but unreserved URL characters
+    UrlEncode,
+    /// Convert to upper case
+    Upper,
+    /// Convert to lower case
+    Lower,
+}
+
+impl Transform {
+    /// Applies the transform to `text`, read from `path` if it came from a file.
+    pub fn apply(self, text: &str, path: Option<&str>) -> String {
+        match self {
+            Transform::Trim => text.trim().to_string(),
+            Transform::Dedent => dedent(text),
+            Transform::StripAnsi => ansi_escape().replace_all(text, "").into_owned(),
+            Transform::Collapse => collapse(text),
+            Transform::Join => text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join(" "),
+            Transform::Fence => {
+                let language = match path.map(language_tag) {
+                    Some(tag) if !tag.is_empty() => tag,
+                    _ => detect_language(text),
+                };
+                code_fence(text, language)
+            }
+            Transform::ShellQuote => format!("'{}'", text.replace('\'', r"'\''")),
+            Transform::JsonEscape => {
+                let quoted = serde_json::Value::from(text).to_string();
+                quoted[1..quoted.len() - 1].to_string()
+            }
+            Transform::Base64 => encode_binary(text.as_bytes(), BinaryFormat::Base64),
+            Transform::UrlEncode => url_encode(text),
+            Transform::Upper => text.to_uppercase(),
+            Transform::Lower => text.to_lowercase(),
+        }
+    }
+}
+
+/// Applies `transforms` to `text` in order.
+pub fn apply_all(text: String, transforms: &[Transform], path: Option<&str>) -> String {
+    transforms
+        .iter()
+        .fold(text, |text, transform| transform.apply(&text, path))
+}
+
+/// Parses a comma-separated list of transforms such as `dedent,fence`.
+pub fn parse_transforms(list: &str) -> Result<Vec<Transform>, ZpError> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            Transform::from_str(name, true)
+                .map_err(|_| ZpError::Parse(format!("unknown transform '{}'", name)))
+        })
+        .collect()
+}
+
+/// Formats `transforms` the way [`parse_transforms`] reads them.
+pub fn format_transforms(transforms: &[Transform]) -> String {
+    transforms
+        .iter()
+        .filter_map(|transform| transform.to_possible_value())
+        .map(|value| value.get_name().to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn ansi_escape() -> &'static Regex {
+    static ANSI_ESCAPE: OnceLock<Regex> = OnceLock::new();
+    ANSI_ESCAPE.get_or_init(|| {
+        // CSI sequences, OSC sequences ended by BEL or ST, and two-byte escapes
+        Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[@-Z\\-_]")
+            .expect("valid ANSI escape regex")
+    })
+}
+
+fn dedent(text: &str) -> String {
+    let indent = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .reduce(|common, indent| {
+            let shared = common
+                .chars()
+                .zip(indent.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(c, _)| c.len_utf8())
+                .sum();
+            &common[..shared]
+        })
+        .unwrap_or("");
+
+    let mut dedented: Vec<&str> = text
+        .lines()
+        .map(|line| line.strip_prefix(indent).unwrap_or(line.trim_start()))
+        .collect();
+    if text.ends_with('\n') {
+        dedented.push("");
+    }
+    dedented.join("\n")
+}
+
+fn collapse(text: &str) -> String {
+    let mut lines: Vec<String> = vec![];
+    for line in text.lines() {
+        let line = line.split([' ', '\t']).filter(|word| !word.is_empty());
+        let line = line.collect::<Vec<_>>().join(" ");
+        if line.is_empty() && lines.last().is_some_and(String::is_empty) {
+            continue;
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+fn url_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Guesses the Markdown language of text that didn't come from a file.
+fn detect_language(text: &str) -> &'static str {
+    let trimmed = text.trim_start();
+    if let Some(shebang) = trimmed.lines().next().and_then(|l| l.strip_prefix("#!")) {
+        let interpreter = shebang
+            .split_whitespace()
+            .find(|word| !word.ends_with("/env"))
+            .and_then(|program| program.rsplit('/').next())
+            .unwrap_or_default();
+        return match interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+            "sh" | "bash" | "zsh" => "bash",
+            "python" => "python",
+            "node" => "javascript",
+            "ruby" => "ruby",
+            "perl" => "perl",
+            _ => "",
+        };
+    }
+    if trimmed.starts_with("<?xml") {
+        "xml"
+    } else if ["<html", "<!doctype"].iter().any(|tag| {
+        trimmed
+            .get(..tag.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(tag))
+    }) {
+        "html"
+    } else if trimmed.starts_with(['{', '['])
+        && serde_json::from_str::<serde_json::Value>(text).is_ok()
+    {
+        "json"
+    } else {
+        ""
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whitespace_transforms() {
+        let text = "    fn main() {\n        zp();\n    }\n";
+        assert_eq!(
+            Transform::Dedent.apply(text, None),
+            "fn main() {\n    zp();\n}\n"
+        );
+        assert_eq!(Transform::Trim.apply("  a b \n", None), "a b");
+        assert_eq!(
+            Transform::Collapse.apply("a  \t b\n\n\n c", None),
+            "a b\n\nc"
+        );
+        assert_eq!(Transform::Join.apply(" a\n\n  b \nc\n", None), "a b c");
+        assert_eq!(
+            Transform::StripAnsi.apply("\x1b[1;31merror\x1b[0m: \x1b]8;;url\x07x", None),
+            "error: x"
+        );
+    }
+
+    #[test]
+    fn test_escaping_transforms() {
+        assert_eq!(Transform::ShellQuote.apply("it's", None), r"'it'\''s'");
+        assert_eq!(
+            Transform::JsonEscape.apply("say \"hi\"\n", None),
+            r#"say \"hi\"\n"#
+        );
+        assert_eq!(Transform::Base64.apply("zp", None), "enA=");
+        assert_eq!(Transform::UrlEncode.apply("a b/ü", None), "a%20b%2F%C3%BC");
+        assert_eq!(Transform::Upper.apply("Zp", None), "ZP");
+        assert_eq!(Transform::Lower.apply("Zp", None), "zp");
+    }
+
+    #[test]
+    fn test_fence_detects_language() {
+        assert_eq!(
+            Transform::Fence.apply("fn main() {}\n", Some("src/main.rs")),
+            "```rust\nfn main() {}\n```"
+        );
+        assert_eq!(
+            Transform::Fence.apply("#!/usr/bin/env python3\nprint()", None),
+            "```python\n#!/usr/bin/env python3\nprint()\n```"
+        );
+        assert_eq!(
+            Transform::Fence.apply("{\"a\": 1}", None),
+            "```json\n{\"a\": 1}\n```"
+        );
+        assert_eq!(
+            Transform::Fence.apply("<HTML><body></body>", None),
+            "```html\n<HTML><body></body>\n```"
+        );
+        // Multibyte characters across the tag length must not panic
+        assert_eq!(
+            Transform::Fence.apply("abcdé world", None),
+            "```\nabcdé world\n```"
+        );
+    }
+
+    #[test]
+    fn test_parse_and_apply_in_order() {
+        let transforms = parse_transforms("dedent, fence").unwrap();
+        assert_eq!(transforms, vec![Transform::Dedent, Transform::Fence]);
+        assert_eq!(
+            apply_all("  x\n".to_string(), &transforms, Some("a.py")),
+            "```python\nx\n```"
+        );
+        assert_eq!(format_transforms(&transforms), "dedent,fence");
+        assert!(parse_transforms("dedent,nope").is_err());
+    }
+}
@@ -0,0 +1,891 @@
+use crate::blob::{blobs_dir, load_image, read_png};
+use crate::config::ZpConfig;
+use crate::error::ZpError;
+use crate::history::{
+    format_elapsed_time, insert_by_timestamp, load_clipboard_history, parse_entry_time,
+    save_clipboard_history, update_clipboard_history, ClipboardHistoryEntry, EntrySource,
+};
+use crate::provider::open_clipboard;
+use crate::secrets::reveal;
+use crate::transform::{apply_all, format_transforms, parse_transforms, Transform};
+use chrono::{Duration, Local};
+use crossterm::{
+    event::{self, KeyCode, KeyEvent},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::text::{Line, Span};
+use ratatui::{
+    backend::CrosstermBackend,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    Terminal,
+};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Lines or columns moved per preview scroll step.
+const PREVIEW_PAGE: u16 = 5;
+
+/// The viewer is drawn on the controlling terminal rather than stdout, so it keeps
+/// working when stdout is piped and `--print` output stays clean.
+#[cfg(not(windows))]
+const TTY_PATH: &str = "/dev/tty";
+#[cfg(windows)]
+const TTY_PATH: &str = "CONOUT$";
+
+type Tui = Terminal<CrosstermBackend<File>>;
+
+/// Opens the history viewer and copies the chosen entry, or writes it to stdout
+/// when `print` is set.
+///
+/// `transforms` are applied to the chosen entry and can be changed in the viewer with `t`.
+pub fn print_clipboard_history(print: bool, transforms: &[Transform]) -> Result<(), ZpError> {
+    let entries = load_clipboard_history().map_err(|e| {
+        eprintln!("Failed to load clipboard history: {}", e);
+        e
+    })?;
+
+    let Some((entry, transforms)) = pick_entry(entries, transforms)? else {
+        return Ok(());
+    };
+    let secrets = ZpConfig::load()?.secrets;
+
+    if !transforms.is_empty() {
+        if entry.is_image() {
+            return Err(ZpError::Parse("images can't be transformed".to_string()));
+        }
+        let text = apply_all(reveal(&entry, &secrets)?, &transforms, None);
+        if print {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(text.as_bytes())?;
+            stdout.flush()?;
+        } else {
+            open_clipboard()?
+                .set_text(&text)
+                .map_err(ZpError::Clipboard)?;
+            if entry.encrypted.is_some() {
+                // A transform can hide the secret from the scanner, e.g. by encoding
+                // it, so neither the text nor a new entry for it is let out
+                eprintln!("Not saved to history: entry contains a secret");
+                println!("Copied: {} bytes", text.len());
+            } else {
+                // The transformed text is new, so it's recorded like any other copy,
+                // and scanned for secrets on the way
+                println!("Copied: {}", text);
+                save_clipboard_history(ClipboardHistoryEntry::new(text, EntrySource::Manual))?;
+            }
+        }
+        return Ok(());
+    }
+
+    if print {
+        let mut stdout = io::stdout().lock();
+        if entry.is_image() {
+            stdout.write_all(&read_png(&blobs_dir(), &entry)?)?;
+        } else {
+            stdout.write_all(reveal(&entry, &secrets)?.as_bytes())?;
+        }
+        stdout.flush()?;
+        Ok(())
+    } else {
+        let mut clipboard = open_clipboard()?;
+        if entry.is_image() {
+            clipboard
+                .set_image(load_image(&blobs_dir(), &entry)?)
+                .map_err(ZpError::Clipboard)?;
+        } else {
+            clipboard
+                .set_text(&reveal(&entry, &secrets)?)
+                .map_err(ZpError::Clipboard)?;
+        }
+        // Shows the redacted content, keeping decrypted secrets off the screen
+        println!("Copied: {}", entry.content);
+        Ok(())
+    }
+}
+
+/// Runs the viewer on the terminal and returns the entry chosen with Enter, along
+/// with the transforms to apply to it.
+fn pick_entry(
+    entries: Vec<ClipboardHistoryEntry>,
+    transforms: &[Transform],
+) -> Result<Option<(ClipboardHistoryEntry, Vec<Transform>)>, ZpError> {
+    let mut tty = open_tty()?;
+
+    enable_raw_mode()?;
+    execute!(tty, EnterAlternateScreen)?;
+
+    let mut terminal = Terminal::new(CrosstermBackend::new(tty))?;
+    let result = run_app(&mut terminal, entries, transforms);
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    result
+}
+
+fn open_tty() -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(TTY_PATH)
+        .map_err(|e| io::Error::new(e.kind(), format!("Cannot open {}: {}", TTY_PATH, e)))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Normal,
+    Search,
+    /// Editing the comma-separated list of transforms.
+    Transform,
+}
+
+/// Only show entries copied within a recent window.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AgeFilter {
+    All,
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl AgeFilter {
+    fn next(self) -> Self {
+        match self {
+            AgeFilter::All => AgeFilter::Hour,
+            AgeFilter::Hour => AgeFilter::Day,
+            AgeFilter::Day => AgeFilter::Week,
+            AgeFilter::Week => AgeFilter::Month,
+            AgeFilter::Month => AgeFilter::All,
+        }
+    }
+
+    fn max_age(self) -> Option<Duration> {
+        match self {
+            AgeFilter::All => None,
+            AgeFilter::Hour => Some(Duration::hours(1)),
+            AgeFilter::Day => Some(Duration::days(1)),
+            AgeFilter::Week => Some(Duration::weeks(1)),
+            AgeFilter::Month => Some(Duration::days(30)),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            AgeFilter::All => "all",
+            AgeFilter::Hour => "<1h",
+            AgeFilter::Day => "<24h",
+            AgeFilter::Week => "<7d",
+            AgeFilter::Month => "<30d",
+        }
+    }
+}
+
+fn next_source_filter(filter: Option<EntrySource>) -> Option<EntrySource> {
+    match filter {
+        None => Some(EntrySource::Manual),
+        Some(EntrySource::Manual) => Some(EntrySource::Daemon),
+        Some(EntrySource::Daemon) => Some(EntrySource::Sync),
+        Some(_) => None,
+    }
+}
+
+fn source_label(filter: Option<EntrySource>) -> &'static str {
+    match filter {
+        None => "all",
+        Some(EntrySource::Unknown) => "unknown",
+        Some(EntrySource::Manual) => "manual",
+        Some(EntrySource::Daemon) => "daemon",
+        Some(EntrySource::Sync) => "sync",
+    }
+}
+
+/// An entry passing the current filters, with the character positions matched by the search.
+struct Match {
+    index: usize,
+    positions: Vec<usize>,
+}
+
+struct App {
+    entries: Vec<ClipboardHistoryEntry>,
+    /// Entry contents flattened to a single line, as displayed and searched.
+    lines: Vec<String>,
+    matcher: SkimMatcherV2,
+    matches: Vec<Match>,
+    selected: usize,
+    offset: usize,
+    mode: Mode,
+    query: String,
+    age_filter: AgeFilter,
+    source_filter: Option<EntrySource>,
+    /// Vertical and horizontal scroll of the preview pane.
+    preview_scroll: (u16, u16),
+    /// Wrap long lines in the preview instead of showing them raw.
+    wrap: bool,
+    /// The most recently deleted entry, restored with `u`.
+    last_deleted: Option<ClipboardHistoryEntry>,
+    /// Feedback for the last action, shown instead of the key hints.
+    status: Option<String>,
+    /// Transforms applied to the entry copied with Enter.
+    transforms: Vec<Transform>,
+    /// The transform list being typed after `t`.
+    transform_input: String,
+}
+
+impl App {
+    fn new(entries: Vec<ClipboardHistoryEntry>) -> Self {
+        let mut app = Self {
+            entries: vec![],
+            lines: vec![],
+            matcher: SkimMatcherV2::default(),
+            matches: vec![],
+            selected: 0,
+            offset: 0,
+            mode: Mode::Normal,
+            query: String::new(),
+            age_filter: AgeFilter::All,
+            source_filter: None,
+            preview_scroll: (0, 0),
+            wrap: true,
+            last_deleted: None,
+            status: None,
+            transforms: vec![],
+            transform_input: String::new(),
+        };
+        app.set_entries(entries);
+        app.refilter();
+        app
+    }
+
+    fn set_entries(&mut self, entries: Vec<ClipboardHistoryEntry>) {
+        self.lines = entries
+            .iter()
+            .map(|entry| entry.content.replace(['\n', '\r', '\t'], " "))
+            .collect();
+        self.entries = entries;
+    }
+
+    /// Reloads the history after it was changed, keeping the selection on `keep_id` if present.
+    fn reload(&mut self, keep_id: Option<&str>) -> Result<(), ZpError> {
+        self.set_entries(load_clipboard_history()?);
+        self.refilter();
+        if let Some(position) = keep_id.and_then(|id| {
+            self.matches
+                .iter()
+                .position(|m| self.entries[m.index].id == id)
+        }) {
+            self.select(position);
+        }
+        Ok(())
+    }
+
+    fn delete_selected(&mut self) -> Result<(), ZpError> {
+        let Some(id) = self.selected_entry().map(|entry| entry.id.clone()) else {
+            return Ok(());
+        };
+
+        let removed = update_clipboard_history(|history| {
+            let position = history.iter().position(|entry| entry.id == id)?;
+            Some(history.remove(position))
+        })?;
+
+        if let Some(entry) = removed {
+            self.last_deleted = Some(entry);
+            self.status = Some("Deleted entry, press u to undo".to_string());
+        }
+        let previous = self.selected.saturating_sub(1);
+        self.reload(None)?;
+        self.select(previous.min(self.matches.len().saturating_sub(1)));
+        Ok(())
+    }
+
+    fn undo_delete(&mut self) -> Result<(), ZpError> {
+        let Some(entry) = self.last_deleted.take() else {
+            self.status = Some("Nothing to undo".to_string());
+            return Ok(());
+        };
+
+        let id = entry.id.clone();
+        update_clipboard_history(|history| insert_by_timestamp(history, entry))?;
+        self.status = Some("Restored deleted entry".to_string());
+        self.reload(Some(&id))
+    }
+
+    fn toggle_pin_selected(&mut self) -> Result<(), ZpError> {
+        let Some(id) = self.selected_entry().map(|entry| entry.id.clone()) else {
+            return Ok(());
+        };
+
+        let pinned = update_clipboard_history(|history| {
+            let entry = history.iter_mut().find(|entry| entry.id == id)?;
+            entry.pinned = !entry.pinned;
+            Some(entry.pinned)
+        })?;
+
+        self.status = match pinned {
+            Some(true) => Some("Pinned entry".to_string()),
+            Some(false) => Some("Unpinned entry".to_string()),
+            None => None,
+        };
+        self.reload(Some(&id))
+    }
+
+    /// Recomputes the visible entries from the search query and filters.
+    fn refilter(&mut self) {
+        let now = Local::now();
+        let max_age = self.age_filter.max_age();
+
+        self.matches = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                max_age.is_none_or(|max_age| now - parse_entry_time(&entry.timestamp) <= max_age)
+            })
+            .filter(|(_, entry)| {
+                self.source_filter
+                    .is_none_or(|source| entry.source == source)
+            })
+            .filter_map(|(index, _)| {
+                if self.query.is_empty() {
+                    return Some(Match {
+                        index,
+                        positions: vec![],
+                    });
+                }
+                self.matcher
+                    .fuzzy_indices(&self.lines[index], &self.query)
+                    .map(|(_, positions)| Match { index, positions })
+            })
+            .collect();
+
+        // Pinned entries go to the top, otherwise keep chronological order
+        let entries = &self.entries;
+        self.matches.sort_by_key(|m| !entries[m.index].pinned);
+
+        // Start at the most recent match
+        self.select(self.matches.len().saturating_sub(1));
+    }
+
+    fn select(&mut self, selected: usize) {
+        self.selected = selected;
+        self.preview_scroll = (0, 0);
+    }
+
+    fn selected_entry(&self) -> Option<&ClipboardHistoryEntry> {
+        self.matches
+            .get(self.selected)
+            .map(|m| &self.entries[m.index])
+    }
+
+    fn title(&self) -> String {
+        let mut title = String::from(" zp ");
+        if self.mode == Mode::Search {
+            title.push_str(&format!("│ /{}█ ", self.query));
+        } else if !self.query.is_empty() {
+            title.push_str(&format!("│ /{} ", self.query));
+        }
+        if self.age_filter != AgeFilter::All {
+            title.push_str(&format!("│ age: {} ", self.age_filter.label()));
+        }
+        if self.source_filter.is_some() {
+            title.push_str(&format!("│ source: {} ", source_label(self.source_filter)));
+        }
+        if self.mode == Mode::Transform {
+            title.push_str(&format!("│ transform: {}█ ", self.transform_input));
+        } else if !self.transforms.is_empty() {
+            title.push_str(&format!(
+                "│ transform: {} ",
+                format_transforms(&self.transforms)
+            ));
+        }
+        title.push_str(&format!("│ {}/{} ", self.matches.len(), self.entries.len()));
+        title
+    }
+}
+
+/// Splits `text` into spans, highlighting the characters at `positions`.
+fn highlight_spans(text: &str, positions: &[usize], max_chars: usize) -> Vec<Span<'static>> {
+    let normal = Style::default().fg(Color::White);
+    let highlighted = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+
+    let mut spans = Vec::new();
+    let mut current = String::new();
+    let mut current_is_match = false;
+    let mut positions = positions.iter().peekable();
+
+    for (i, c) in text.chars().take(max_chars).enumerate() {
+        let is_match = positions.next_if(|&&p| p == i).is_some();
+        if is_match != current_is_match && !current.is_empty() {
+            let style = if current_is_match {
+                highlighted
+            } else {
+                normal
+            };
+            spans.push(Span::styled(std::mem::take(&mut current), style));
+        }
+        current_is_match = is_match;
+        current.push(c);
+    }
+
+    if !current.is_empty() {
+        let style = if current_is_match {
+            highlighted
+        } else {
+            normal
+        };
+        spans.push(Span::styled(current, style));
+    }
+    spans
+}
+
+/// Builds the preview of `entry`: its metadata followed by the numbered content lines.
+fn preview_lines(entry: &ClipboardHistoryEntry) -> Vec<Line<'static>> {
+    let label = Style::default().fg(Color::DarkGray);
+    let gutter = Style::default().fg(Color::DarkGray);
+    let content_lines: Vec<&str> = entry.content.lines().collect();
+
+    let mut metadata = vec![
+        (
+            "Copied",
+            format!(
+                "{} ({})",
+                parse_entry_time(&entry.timestamp).format("%Y-%m-%d %H:%M:%S"),
+                format_elapsed_time(&entry.timestamp).trim()
+            ),
+        ),
+        (
+            "Size",
+            match entry.dimensions {
+                Some((width, height)) => {
+                    format!("{}x{} pixels, {} bytes", width, height, entry.size)
+                }
+                None => format!(
+                    "{} bytes, {} lines",
+                    entry.content.len(),
+                    content_lines.len()
+                ),
+            },
+        ),
+        ("Source", source_label(Some(entry.source)).to_string()),
+        ("Type", entry.mime_type.clone()),
+    ];
+    if entry.use_count > 1 {
+        metadata.push(("Uses", entry.use_count.to_string()));
+    }
+    if let Some(peer_id) = &entry.peer_id {
+        metadata.push(("Peer", peer_id.clone()));
+    }
+    for source in &entry.sources {
+        let location = match source.lines {
+            Some((start, end)) => format!("{}:{}-{}", source.path, start, end),
+            None => source.path.clone(),
+        };
+        metadata.push(("From", location));
+    }
+    if entry.pinned {
+        metadata.push(("Pinned", "yes".to_string()));
+    }
+    if let Some(expires_at) = &entry.expires_at {
+        metadata.push((
+            "Expires",
+            parse_entry_time(expires_at)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+        ));
+    }
+    metadata.push(("Id", entry.id.clone()));
+
+    let mut lines: Vec<Line> = metadata
+        .into_iter()
+        .map(|(name, value)| {
+            Line::from(vec![
+                Span::styled(format!("{:>7}: ", name), label),
+                Span::raw(value),
+            ])
+        })
+        .collect();
+    lines.push(Line::raw(""));
+
+    if entry.is_image() {
+        lines.push(Line::styled(
+            "Press Enter to copy the image, or use `zp history show --output`",
+            label,
+        ));
+        return lines;
+    }
+
+    let number_width = content_lines.len().to_string().len();
+    lines.extend(content_lines.iter().enumerate().map(|(i, line)| {
+        Line::from(vec![
+            Span::styled(
+                format!("{:>width$} │ ", i + 1, width = number_width),
+                gutter,
+            ),
+            Span::raw(line.replace('\t', "    ")),
+        ])
+    }));
+    lines
+}
+
+/// Opens the selected entry in the editor and saves the edited text as a new entry.
+fn edit_selected(app: &mut App, terminal: &mut Tui) -> Result<(), ZpError> {
+    let Some(entry) = app.selected_entry() else {
+        return Ok(());
+    };
+    // The original of an entry with encrypted secrets, not its redacted text
+    let content = reveal(entry, &ZpConfig::load()?.secrets)?;
+    let Some(edited) = edit_in_editor(terminal, &content)? else {
+        app.status = Some("No changes made".to_string());
+        return Ok(());
+    };
+
+    let entry = ClipboardHistoryEntry::new(edited, EntrySource::Manual);
+    let id = entry.id.clone();
+    save_clipboard_history(entry)?;
+    app.reload(Some(&id))?;
+    app.status = Some(if app.entries.iter().any(|entry| entry.id == id) {
+        "Saved edited text as a new entry".to_string()
+    } else {
+        "Edited text wasn't added as a new entry".to_string()
+    });
+    Ok(())
+}
+
+/// Opens `content` in `$VISUAL` or `$EDITOR` and returns the edited text if it changed.
+fn edit_in_editor(terminal: &mut Tui, content: &str) -> io::Result<Option<String>> {
+    // A new file with a random name, readable only by the user, since the content
+    // can be a decrypted secret. It's removed when dropped.
+    let mut file = tempfile::Builder::new()
+        .prefix("zp-edit-")
+        .suffix(".txt")
+        .tempfile()?;
+    file.write_all(content.as_bytes())?;
+    file.flush()?;
+    let path = file.path().to_path_buf();
+
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    // Hand the terminal over to the editor while it runs
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    // Editors are often configured with arguments, e.g. "code --wait"
+    let mut args = editor.split_whitespace();
+    let status = Command::new(args.next().unwrap_or("vi"))
+        .args(args)
+        .arg(&path)
+        // Attach the editor to the terminal even when zp's own stdio is piped
+        .stdin(Stdio::from(open_tty()?))
+        .stdout(Stdio::from(open_tty()?))
+        .status();
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    terminal.clear()?;
+
+    let edited = status.and_then(|status| {
+        if status.success() {
+            fs::read_to_string(&path)
+        } else {
+            Err(io::Error::other(format!(
+                "{} exited with {}",
+                editor, status
+            )))
+        }
+    });
+    drop(file);
+
+    let edited = edited?;
+    Ok((edited != content).then_some(edited))
+}
+
+fn run_app(
+    terminal: &mut Tui,
+    entries: Vec<ClipboardHistoryEntry>,
+    transforms: &[Transform],
+) -> Result<Option<(ClipboardHistoryEntry, Vec<Transform>)>, ZpError> {
+    let mut app = App::new(entries);
+    app.transforms = transforms.to_vec();
+
+    loop {
+        terminal.draw(|f| {
+            let size = f.area();
+            let app_height = size.height / 2;
+            let max_visible_items = app_height.saturating_sub(2) as usize; // Account for borders
+
+            let [list_area, preview_area] =
+                Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(
+                    Rect {
+                        y: size.height - app_height,
+                        width: size.width,
+                        height: app_height,
+                        ..size
+                    },
+                );
+
+            // Ensure offset keeps the selected item in view
+            if app.selected >= app.offset + max_visible_items {
+                app.offset = app
+                    .selected
+                    .saturating_sub(max_visible_items)
+                    .saturating_add(1);
+            } else if app.selected < app.offset {
+                app.offset = app.selected;
+            }
+
+            // Calculate the visible entries
+            let visible_matches =
+                &app.matches[app.offset..app.matches.len().min(app.offset + max_visible_items)];
+
+            // Render list items
+            let items: Vec<ListItem> = visible_matches
+                .iter()
+                .enumerate()
+                .map(|(i, m)| {
+                    let actual_index = app.offset + i;
+                    let entry = &app.entries[m.index];
+                    let elapsed = format_elapsed_time(&entry.timestamp);
+                    let elapsed_styled = Span::styled(elapsed, Style::default().fg(Color::Green));
+
+                    let highlight_symbol = if actual_index == app.selected {
+                        Span::styled(
+                            "> ",
+                            Style::default()
+                                .fg(Color::Green)
+                                .add_modifier(Modifier::BOLD),
+                        )
+                    } else {
+                        Span::raw("  ")
+                    };
+
+                    let pin = if entry.pinned {
+                        Span::styled(" * ", Style::default().fg(Color::Yellow))
+                    } else {
+                        Span::raw("   ")
+                    };
+
+                    let mut spans = vec![highlight_symbol, elapsed_styled, pin];
+                    spans.extend(highlight_spans(
+                        &app.lines[m.index],
+                        &m.positions,
+                        list_area.width as usize,
+                    ));
+                    let line = Line::from(spans);
+
+                    if actual_index == app.selected {
+                        ListItem::new(line).style(Style::default().bg(Color::DarkGray))
+                    } else {
+                        ListItem::new(line)
+                    }
+                })
+                .collect();
+
+            let list = List::new(items).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(app.title())
+                    .title_bottom(app.status.clone().unwrap_or_else(|| {
+                        " / search │ a age │ s source │ p pin │ d delete │ u undo │ e edit │ t transform │ Enter copy │ Esc quit ".to_string()
+                    }))
+                    .style(Style::default().bg(Color::Black).fg(Color::White)),
+            );
+            f.render_widget(list, list_area);
+
+            let preview_block = Block::default()
+                .borders(Borders::ALL)
+                .title(if app.wrap {
+                    " preview (wrapped) "
+                } else {
+                    " preview (raw) "
+                })
+                .title_bottom(" PgUp/PgDn scroll │ ←/→ pan │ w wrap ")
+                .style(Style::default().bg(Color::Black).fg(Color::White));
+            let preview =
+                Paragraph::new(app.selected_entry().map(preview_lines).unwrap_or_default())
+                    .block(preview_block)
+                    .scroll(app.preview_scroll);
+            let preview = if app.wrap {
+                preview.wrap(Wrap { trim: false })
+            } else {
+                preview
+            };
+            f.render_widget(preview, preview_area);
+        })?;
+
+        // Handle input
+        if let event::Event::Key(KeyEvent { code, .. }) = event::read()? {
+            app.status = None;
+            match (app.mode, code) {
+                (_, KeyCode::Up) if app.selected > 0 => {
+                    app.select(app.selected - 1);
+                }
+                (_, KeyCode::Down) if app.selected + 1 < app.matches.len() => {
+                    app.select(app.selected + 1);
+                }
+                (_, KeyCode::PageDown) => {
+                    app.preview_scroll.0 = app.preview_scroll.0.saturating_add(PREVIEW_PAGE);
+                }
+                (_, KeyCode::PageUp) => {
+                    app.preview_scroll.0 = app.preview_scroll.0.saturating_sub(PREVIEW_PAGE);
+                }
+                (_, KeyCode::Right) if !app.wrap => {
+                    app.preview_scroll.1 = app.preview_scroll.1.saturating_add(PREVIEW_PAGE);
+                }
+                (_, KeyCode::Left) if !app.wrap => {
+                    app.preview_scroll.1 = app.preview_scroll.1.saturating_sub(PREVIEW_PAGE);
+                }
+                (Mode::Search, KeyCode::Char(c)) => {
+                    app.query.push(c);
+                    app.refilter();
+                }
+                (Mode::Search, KeyCode::Backspace) => {
+                    app.query.pop();
+                    app.refilter();
+                }
+                (Mode::Search, KeyCode::Enter) => app.mode = Mode::Normal,
+                (Mode::Search, KeyCode::Esc) => {
+                    app.query.clear();
+                    app.mode = Mode::Normal;
+                    app.refilter();
+                }
+                (Mode::Transform, KeyCode::Char(c)) => app.transform_input.push(c),
+                (Mode::Transform, KeyCode::Backspace) => {
+                    app.transform_input.pop();
+                }
+                (Mode::Transform, KeyCode::Enter) => match parse_transforms(&app.transform_input) {
+                    Ok(transforms) => {
+                        app.transforms = transforms;
+                        app.mode = Mode::Normal;
+                    }
+                    Err(e) => app.status = Some(e.to_string()),
+                },
+                (Mode::Transform, KeyCode::Esc) => app.mode = Mode::Normal,
+                (Mode::Normal, KeyCode::Char('/')) => app.mode = Mode::Search,
+                (Mode::Normal, KeyCode::Char('t')) => {
+                    app.transform_input = format_transforms(&app.transforms);
+                    app.mode = Mode::Transform;
+                }
+                (Mode::Normal, KeyCode::Char('a')) => {
+                    app.age_filter = app.age_filter.next();
+                    app.refilter();
+                }
+                (Mode::Normal, KeyCode::Char('s')) => {
+                    app.source_filter = next_source_filter(app.source_filter);
+                    app.refilter();
+                }
+                (Mode::Normal, KeyCode::Char('w')) => {
+                    app.wrap = !app.wrap;
+                    app.preview_scroll = (0, 0);
+                }
+                (Mode::Normal, KeyCode::Char('d')) => {
+                    if let Err(e) = app.delete_selected() {
+                        app.status = Some(format!("Failed to delete entry: {}", e));
+                    }
+                }
+                (Mode::Normal, KeyCode::Char('u')) => {
+                    if let Err(e) = app.undo_delete() {
+                        app.status = Some(format!("Failed to restore entry: {}", e));
+                    }
+                }
+                (Mode::Normal, KeyCode::Char('p')) => {
+                    if let Err(e) = app.toggle_pin_selected() {
+                        app.status = Some(format!("Failed to pin entry: {}", e));
+                    }
+                }
+                (Mode::Normal, KeyCode::Char('e'))
+                    if app.selected_entry().is_some_and(|entry| entry.is_image()) =>
+                {
+                    app.status = Some("Images can't be edited".to_string());
+                }
+                (Mode::Normal, KeyCode::Char('e')) => {
+                    // Saving can fail, e.g. on a locked history, without leaving the viewer
+                    if let Err(e) = edit_selected(&mut app, terminal) {
+                        app.status = Some(format!("Failed to edit entry: {}", e));
+                    }
+                }
+                (Mode::Normal, KeyCode::Enter)
+                    if !app.transforms.is_empty()
+                        && app.selected_entry().is_some_and(|entry| entry.is_image()) =>
+                {
+                    app.status = Some("Images can't be transformed".to_string());
+                }
+                (Mode::Normal, KeyCode::Enter) => {
+                    if let Some(entry) = app.selected_entry() {
+                        return Ok(Some((entry.clone(), app.transforms)));
+                    }
+                }
+                (Mode::Normal, KeyCode::Esc) => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_and_source_filters_compose() {
+        let mut entries: Vec<_> = ["cargo build", "git commit", "cargo test"]
+            .iter()
+            .map(|c| ClipboardHistoryEntry::new(c.to_string(), EntrySource::Manual))
+            .collect();
+        entries[2].source = EntrySource::Daemon;
+
+        let mut app = App::new(entries);
+        app.query = "cgo".to_string();
+        app.refilter();
+        let found: Vec<usize> = app.matches.iter().map(|m| m.index).collect();
+        assert_eq!(found, vec![0, 2]);
+        assert_eq!(app.matches[0].positions, vec![0, 3, 4]);
+
+        app.source_filter = Some(EntrySource::Daemon);
+        app.refilter();
+        assert_eq!(app.selected_entry().unwrap().content, "cargo test");
+    }
+
+    #[test]
+    fn test_pinned_entries_sort_first() {
+        let mut entries: Vec<_> = ["first", "second", "third"]
+            .iter()
+            .map(|c| ClipboardHistoryEntry::new(c.to_string(), EntrySource::Manual))
+            .collect();
+        entries[1].pinned = true;
+
+        let app = App::new(entries);
+        let order: Vec<usize> = app.matches.iter().map(|m| m.index).collect();
+        assert_eq!(order, vec![1, 0, 2]);
+        assert_eq!(app.selected_entry().unwrap().content, "third");
+    }
+
+    #[test]
+    fn test_preview_numbers_content_lines() {
+        let entry =
+            ClipboardHistoryEntry::new("fn main() {\n\tzp();\n}".to_string(), EntrySource::Manual);
+        let lines = preview_lines(&entry);
+        let text: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+
+        assert!(text.iter().any(|l| l.contains("20 bytes, 3 lines")));
+        let content = &text[text.len() - 3..];
+        assert_eq!(content[0], "1 │ fn main() {");
+        assert_eq!(content[1], "2 │     zp();");
+        assert_eq!(content[2], "3 │ }");
+    }
+
+    #[test]
+    fn test_highlight_spans() {
+        let spans = highlight_spans("cargo", &[0, 1, 4], 80);
+        let text: Vec<&str> = spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(text, vec!["ca", "rg", "o"]);
+    }
+}